@@ -1,8 +1,38 @@
 use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
 use fs_extra::dir::CopyOptions;
 
+fn embed_assets(out_dir: &Path) -> anyhow::Result<()> {
+    let res_dir = Path::new(&env::var("CARGO_MANIFEST_DIR")?).join("res");
+    let content = fs_extra::dir::get_dir_content(&res_dir)?;
+
+    let mut files = content.files;
+    files.sort();
+
+    let mut source = String::from("pub(super) static EMBEDDED_ASSETS: &[(&str, &[u8])] = &[\n");
+    for file in files {
+        let name = Path::new(&file)
+            .strip_prefix(&res_dir)?
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        writeln!(source, "    ({name:?}, include_bytes!({file:?})),")?;
+    }
+    source.push_str("];\n");
+
+    fs::write(out_dir.join("embedded_assets.rs"), source)?;
+    Ok(())
+}
+
 fn main() -> anyhow::Result<()> {
     println!("cargo:rerun-if-changed=res/*");
-    fs_extra::copy_items(&["res/"], env::var("OUT_DIR")?, &CopyOptions::new().overwrite(true))?;
+    let out_dir = env::var("OUT_DIR")?;
+    fs_extra::copy_items(&["res/"], &out_dir, &CopyOptions::new().overwrite(true))?;
+    if env::var_os("CARGO_FEATURE_EMBED_ASSETS").is_some() {
+        embed_assets(Path::new(&out_dir))?;
+    }
     Ok(())
 }
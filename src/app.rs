@@ -0,0 +1,736 @@
+use crate::camera::{
+    Camera,
+    ProjectionMode,
+    View
+};
+use crate::camera_path::{
+    CameraPath,
+    Keyframe
+};
+use crate::controller::{
+    CameraController,
+    FlyController,
+    OrbitController
+};
+use crate::scene::Transform;
+use crate::light::Light;
+use crate::renderer::{
+    Renderer,
+    RenderSettings
+};
+use crate::scene_file::SceneFile;
+use crate::error::{
+    Error,
+    Result
+};
+use crate::vfs::AssetRoots;
+use crate::assets::MissingAssetPolicy;
+use crate::timing::{
+    FixedTimestep,
+    FrameLimiter
+};
+use crate::input::{
+    self,
+    Input,
+    InputBindings
+};
+
+use sdl2::event::{
+    Event,
+    WindowEvent
+};
+use cgmath::{
+    Point3,
+    Deg
+};
+use std::{
+    collections::HashMap,
+    fs,
+    time::{
+        Duration,
+        Instant
+    },
+    path::{
+        Path,
+        PathBuf
+    },
+    thread
+};
+
+pub trait Application {
+    fn setup(&mut self, _renderer: &mut Renderer) -> Result<()> {
+        Ok(())
+    }
+
+    // Called once per fixed simulation step with the step length; the time scale changes how
+    // often steps run, not their length
+    fn update(&mut self, _renderer: &mut Renderer, _deltatime: Duration) {}
+
+    // Returning true marks the event as handled, so it never reaches the input bindings (apart from
+    // releases, which always do)
+    fn event(&mut self, _renderer: &mut Renderer, _event: &Event) -> bool {
+        false
+    }
+
+    fn render(&mut self, _renderer: &mut Renderer) {}
+}
+
+impl Application for () {}
+
+#[derive(Clone, Debug)]
+pub struct WindowConfig {
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+    pub resizable: bool,
+    // Borderless at the desktop resolution; width and height are ignored
+    pub fullscreen: bool
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: String::from("rust-sdl2 demo"),
+            width: 1000,
+            height: 800,
+            resizable: true,
+            fullscreen: false
+        }
+    }
+}
+
+pub struct AppBuilder {
+    window: WindowConfig,
+    backends: wgpu::Backends,
+    present_mode: wgpu::PresentMode,
+    target_fps: Option<u32>,
+    timestep: FixedTimestep,
+    asset_roots: Vec<PathBuf>,
+    missing_asset_policy: MissingAssetPolicy,
+    models: Vec<(String, Vec<Transform>)>,
+    lights: Vec<Light>,
+    camera: Camera,
+    camera_controllers: Vec<Box<dyn CameraController>>,
+    auto_frame: bool,
+    projection_mode: ProjectionMode,
+    input_bindings: InputBindings,
+    camera_path: CameraPath,
+    camera_path_file: Option<PathBuf>,
+    play_camera_path: bool,
+    render_settings: RenderSettings
+}
+
+impl AppBuilder {
+    pub fn new() -> Self {
+        Self {
+            window: WindowConfig::default(),
+            backends: wgpu::Backends::all(),
+            present_mode: wgpu::PresentMode::AutoVsync,
+            target_fps: None,
+            timestep: FixedTimestep::default(),
+            asset_roots: Vec::new(),
+            missing_asset_policy: MissingAssetPolicy::default(),
+            models: Vec::new(),
+            lights: Vec::new(),
+            camera: Camera::new(
+                Point3::new(0.0, 0.0, -5.0),
+                Deg(90.0),
+                Deg(0.0)
+            ),
+            camera_controllers: Vec::new(),
+            auto_frame: false,
+            projection_mode: ProjectionMode::default(),
+            input_bindings: InputBindings::default(),
+            camera_path: CameraPath::new(),
+            camera_path_file: None,
+            play_camera_path: false,
+            render_settings: RenderSettings::default()
+        }
+    }
+
+    pub fn window(mut self, window: WindowConfig) -> Self {
+        self.window = window;
+        self
+    }
+
+    pub fn backends(mut self, backends: wgpu::Backends) -> Self {
+        self.backends = backends;
+        self
+    }
+
+    // Only used by windowed apps
+    pub fn present_mode(mut self, present_mode: wgpu::PresentMode) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+
+    // Caps the frame rate of windowed apps on top of any vsync; None leaves it uncapped
+    pub fn target_fps(mut self, target_fps: Option<u32>) -> Self {
+        self.target_fps = target_fps;
+        self
+    }
+
+    // Length of one simulation step, 1/60 s by default. Panics if step is zero
+    pub fn fixed_timestep(mut self, step: Duration) -> Self {
+        self.timestep = FixedTimestep::new(step);
+        self
+    }
+
+    // Searched in the order given, ahead of the default asset roots
+    pub fn asset_root<P: Into<PathBuf>>(mut self, root: P) -> Self {
+        self.asset_roots.push(root.into());
+        self
+    }
+
+    pub fn missing_asset_policy(mut self, policy: MissingAssetPolicy) -> Self {
+        self.missing_asset_policy = policy;
+        self
+    }
+
+    pub fn model<S: Into<String>>(mut self, filename: S, transforms: Vec<Transform>) -> Self {
+        self.models.push((filename.into(), transforms));
+        self
+    }
+
+    pub fn light(mut self, light: Light) -> Self {
+        self.lights.push(light);
+        self
+    }
+
+    pub fn camera(mut self, camera: Camera) -> Self {
+        self.camera = camera;
+        self
+    }
+
+    // Adds a controller to switch between at runtime; the first one added starts active. Without
+    // any, the app gets a fly controller followed by an orbit controller
+    pub fn camera_controller<C: CameraController + 'static>(mut self, camera_controller: C) -> Self {
+        self.camera_controllers.push(Box::new(camera_controller));
+        self
+    }
+
+    // Frames the whole scene with the starting controller once the app is set up
+    pub fn auto_frame(mut self, auto_frame: bool) -> Self {
+        self.auto_frame = auto_frame;
+        self
+    }
+
+    pub fn projection_mode(mut self, projection_mode: ProjectionMode) -> Self {
+        self.projection_mode = projection_mode;
+        self
+    }
+
+    pub fn input_bindings(mut self, input_bindings: InputBindings) -> Self {
+        self.input_bindings = input_bindings;
+        self
+    }
+
+    pub fn input_bindings_file<P: AsRef<Path>>(self, path: P) -> Result<Self> {
+        Ok(self.input_bindings(InputBindings::load(path)?))
+    }
+
+    pub fn camera_path(mut self, camera_path: CameraPath) -> Self {
+        self.camera_path = camera_path;
+        self
+    }
+
+    // Loads the path if the file exists, and saves to it whenever a keyframe is recorded
+    pub fn camera_path_file<P: Into<PathBuf>>(mut self, path: P) -> Result<Self> {
+        let path = path.into();
+        if path.exists() {
+            self.camera_path = CameraPath::load(&path)?;
+        }
+        self.camera_path_file = Some(path);
+        Ok(self)
+    }
+
+    // Starts playing the camera path once the app is set up, instead of handing the camera to the
+    // starting controller
+    pub fn play_camera_path(mut self, play_camera_path: bool) -> Self {
+        self.play_camera_path = play_camera_path;
+        self
+    }
+
+    pub fn render_settings(mut self, render_settings: RenderSettings) -> Self {
+        self.render_settings = render_settings;
+        self
+    }
+
+    pub fn msaa_samples(mut self, msaa_samples: u32) -> Self {
+        self.render_settings.msaa_samples = msaa_samples;
+        self
+    }
+
+    // Adds the file's models and lights, and takes its camera pose and render settings
+    pub fn scene(mut self, scene: SceneFile) -> Self {
+        for model in scene.models {
+            let transforms = model.instances.into_iter().map(Transform::from).collect();
+            self.models.push((model.path, transforms));
+        }
+        self.lights.extend(scene.lights.into_iter().map(Light::from));
+        self.camera = scene.camera.into();
+        self.projection_mode = scene.camera.projection;
+        self.render_settings = scene.render;
+        self
+    }
+
+    // Loads a scene file and looks up its models next to it, after any roots added so far
+    pub fn scene_file<P: AsRef<Path>>(self, path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let scene = SceneFile::load(path)?;
+        let scene_dir = path.parent().unwrap_or(Path::new("."));
+        Ok(self.asset_root(scene_dir).scene(scene))
+    }
+
+    pub async fn build(self) -> Result<App> {
+        let sdl_context = sdl2::init().map_err(Error::Sdl)?;
+        let video_subsystem = sdl_context.video().map_err(Error::Sdl)?;
+
+        let mut window_builder = video_subsystem.window(&self.window.title, self.window.width, self.window.height);
+        window_builder.position_centered().vulkan().allow_highdpi();
+        if self.window.resizable {
+            window_builder.resizable();
+        }
+        if self.window.fullscreen {
+            window_builder.fullscreen_desktop();
+        }
+        let window = window_builder.build().map_err(|err| Error::Sdl(err.to_string()))?;
+
+        let renderer = Renderer::new_windowed(window, self.backends, self.present_mode, self.camera).await?;
+
+        self.finish(Some(sdl_context), renderer)
+    }
+
+    pub async fn build_headless(self, force_fallback_adapter: bool) -> Result<App> {
+        let renderer = Renderer::new_headless(self.window.width, self.window.height, self.backends, force_fallback_adapter, self.camera).await?;
+
+        self.finish(None, renderer)
+    }
+
+    fn finish(self, sdl_context: Option<sdl2::Sdl>, mut renderer: Renderer) -> Result<App> {
+        renderer.set_asset_roots(AssetRoots::with_defaults(self.asset_roots));
+        renderer.set_missing_asset_policy(self.missing_asset_policy);
+        renderer.set_render_settings(self.render_settings);
+        renderer.set_projection_mode(self.projection_mode);
+        for (filename, transforms) in self.models {
+            let model = renderer.load_model(&filename)?;
+            for transform in transforms {
+                renderer.scene_mut().add(None, transform, Some(model.clone()));
+            }
+        }
+        for light in self.lights {
+            renderer.add_light(light);
+        }
+
+        let mut camera_controllers = self.camera_controllers;
+        if camera_controllers.is_empty() {
+            camera_controllers.push(Box::new(FlyController::default()));
+            camera_controllers.push(Box::new(OrbitController::default()));
+        }
+        camera_controllers[0].activate(renderer.camera());
+
+        Ok(App {
+            sdl_context,
+            renderer,
+            camera_controllers,
+            active_camera_controller: 0,
+            auto_frame: self.auto_frame,
+            input: Input::new(self.input_bindings),
+            camera_path: self.camera_path,
+            camera_path_file: self.camera_path_file,
+            autoplay_camera_path: self.play_camera_path,
+            playback_time: None,
+            since_keyframe: Duration::ZERO,
+            frame_limiter: FrameLimiter::new(self.target_fps),
+            timestep: self.timestep
+        })
+    }
+}
+
+impl Default for AppBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct App {
+    sdl_context: Option<sdl2::Sdl>,
+    renderer: Renderer,
+    camera_controllers: Vec<Box<dyn CameraController>>,
+    active_camera_controller: usize,
+    auto_frame: bool,
+    input: Input,
+    camera_path: CameraPath,
+    camera_path_file: Option<PathBuf>,
+    autoplay_camera_path: bool,
+    // Seconds into the camera path while it is playing
+    playback_time: Option<f32>,
+    // Real time since the last keyframe was recorded, which spaces out the next one
+    since_keyframe: Duration,
+    frame_limiter: FrameLimiter,
+    timestep: FixedTimestep
+}
+
+impl App {
+    const MINIMIZED_FRAME_TIME: Duration = Duration::from_millis(50);
+
+    pub fn renderer(&self) -> &Renderer {
+        &self.renderer
+    }
+
+    pub fn renderer_mut(&mut self) -> &mut Renderer {
+        &mut self.renderer
+    }
+
+    pub fn camera_controller(&self) -> &dyn CameraController {
+        self.camera_controllers[self.active_camera_controller].as_ref()
+    }
+
+    pub fn camera_controller_mut(&mut self) -> &mut dyn CameraController {
+        self.camera_controllers[self.active_camera_controller].as_mut()
+    }
+
+    // Hands the camera to the next controller, wrapping around; also bound to the
+    // switch_camera_controller action while running
+    pub fn switch_camera_controller(&mut self) {
+        self.active_camera_controller = (self.active_camera_controller + 1) % self.camera_controllers.len();
+        let camera = *self.renderer.camera();
+        self.camera_controller_mut().activate(&camera);
+    }
+
+    // Has the active controller fit every model in the scene into view; also bound to the
+    // frame_scene action while running
+    pub fn frame_scene(&mut self) {
+        let Some(bounds) = self.renderer.scene_bounds() else {
+            return;
+        };
+        let (width, height) = self.renderer.size();
+        let mut camera = *self.renderer.camera();
+        self.camera_controller_mut().frame(&mut camera, &bounds, width as f32 / height as f32);
+        *self.renderer.camera_mut() = camera;
+        self.renderer.snap_camera();
+    }
+
+    // Also bound to the toggle_projection action while running
+    pub fn toggle_projection_mode(&mut self) {
+        let mode = match self.renderer.projection_mode() {
+            ProjectionMode::Perspective => ProjectionMode::Orthographic,
+            ProjectionMode::Orthographic => ProjectionMode::Perspective
+        };
+        self.renderer.set_projection_mode(mode);
+    }
+
+    // Turns the camera to an axis-aligned orthographic view of what it is focused on; also bound to
+    // the view_front, view_side and view_top actions while running
+    pub fn set_view(&mut self, view: View) {
+        self.renderer.camera_mut().set_view(view);
+        self.renderer.set_projection_mode(ProjectionMode::Orthographic);
+        self.renderer.snap_camera();
+        let camera = *self.renderer.camera();
+        self.camera_controller_mut().activate(&camera);
+    }
+
+    pub fn camera_path(&self) -> &CameraPath {
+        &self.camera_path
+    }
+
+    pub fn camera_path_mut(&mut self) -> &mut CameraPath {
+        &mut self.camera_path
+    }
+
+    // Adds the camera's pose as a keyframe after the last one, as long after it as has passed since
+    // it was recorded, and saves the path if it came from a file; also bound to the record_keyframe action
+    // while running
+    pub fn record_keyframe(&mut self) -> Result<()> {
+        let time = if self.camera_path.is_empty() {
+            0.0
+        } else {
+            self.camera_path.duration() + self.since_keyframe.as_secs_f32()
+        };
+        self.camera_path.add_keyframe(Keyframe::new(time, self.renderer.camera()));
+        self.since_keyframe = Duration::ZERO;
+        log::info!("Recorded camera keyframe {} at {time:.2} s", self.camera_path.keyframes().len());
+
+        match &self.camera_path_file {
+            Some(path) => self.camera_path.save(path),
+            None => Ok(())
+        }
+    }
+
+    // Plays the camera path from the start in real time, taking the camera from the controller until
+    // it ends; also bound to the play_camera_path action while running, which
+    // stops it again
+    pub fn play_camera_path(&mut self) {
+        let Some(camera) = self.camera_path.sample(0.0) else {
+            log::warn!("The camera path has no keyframes to play");
+            return;
+        };
+        *self.renderer.camera_mut() = camera;
+        self.renderer.snap_camera();
+        self.playback_time = Some(0.0);
+    }
+
+    // Leaves the camera where playback got to and hands it back to the controller
+    pub fn stop_camera_path(&mut self) {
+        if self.playback_time.take().is_some() {
+            let camera = *self.renderer.camera();
+            self.camera_controller_mut().activate(&camera);
+        }
+    }
+
+    pub fn camera_path_playing(&self) -> bool {
+        self.playback_time.is_some()
+    }
+
+    pub fn input(&self) -> &Input {
+        &self.input
+    }
+
+    pub fn input_mut(&mut self) -> &mut Input {
+        &mut self.input
+    }
+
+    pub fn target_fps(&self) -> Option<u32> {
+        self.frame_limiter.target_fps()
+    }
+
+    pub fn set_target_fps(&mut self, target_fps: Option<u32>) {
+        self.frame_limiter.set_target_fps(target_fps);
+    }
+
+    pub fn timestep(&self) -> &FixedTimestep {
+        &self.timestep
+    }
+
+    // Time scale, pause and single stepping; also bound to the slower, faster, pause and single_step
+    // actions while running
+    pub fn timestep_mut(&mut self) -> &mut FixedTimestep {
+        &mut self.timestep
+    }
+
+    fn process_actions(&mut self, event: &Event) {
+        if self.input.action_pressed(input::SWITCH_CAMERA_CONTROLLER, event) {
+            self.switch_camera_controller();
+        }
+        if self.input.action_pressed(input::FRAME_SCENE, event) {
+            self.frame_scene();
+        }
+        if self.input.action_pressed(input::TOGGLE_PROJECTION, event) {
+            self.toggle_projection_mode();
+        }
+        for (action, view) in [(input::VIEW_FRONT, View::Front), (input::VIEW_SIDE, View::Side), (input::VIEW_TOP, View::Top)] {
+            if self.input.action_pressed(action, event) {
+                self.set_view(view);
+            }
+        }
+        if self.input.action_pressed(input::RECORD_KEYFRAME, event) {
+            if let Err(err) = self.record_keyframe() {
+                log::error!("Failed to save the camera path: {err}");
+            }
+        }
+        if self.input.action_pressed(input::PLAY_CAMERA_PATH, event) {
+            if self.camera_path_playing() {
+                self.stop_camera_path();
+            } else {
+                self.play_camera_path();
+            }
+        }
+        if self.input.action_pressed(input::PAUSE, event) {
+            self.timestep.set_paused(!self.timestep.paused());
+        }
+        if self.input.action_pressed(input::SINGLE_STEP, event) {
+            self.timestep.single_step();
+        }
+        if self.input.action_pressed(input::SLOWER, event) {
+            self.timestep.set_time_scale(self.timestep.time_scale() / 2.0);
+        }
+        if self.input.action_pressed(input::FASTER, event) {
+            self.timestep.set_time_scale(self.timestep.time_scale() * 2.0);
+        }
+    }
+
+    // Moves the camera on by one frame, along the camera path while it plays and with the active
+    // controller otherwise. This runs on real time rather than simulation steps, so the camera can
+    // still look around a paused or slowed down simulation
+    fn update_camera(&mut self, frame_time: Duration) {
+        let frame_time = frame_time.min(FixedTimestep::MAX_FRAME_TIME);
+        let Some(time) = self.playback_time else {
+            self.since_keyframe += frame_time;
+            self.camera_controllers[self.active_camera_controller].update_camera(self.renderer.camera_mut(), &mut self.input, &frame_time);
+            return;
+        };
+
+        let time = time + frame_time.as_secs_f32();
+        if let Some(camera) = self.camera_path.sample(time) {
+            *self.renderer.camera_mut() = camera;
+        }
+        self.playback_time = Some(time);
+        if time >= self.camera_path.duration() {
+            self.stop_camera_path();
+        }
+    }
+
+    pub fn run<A: Application>(mut self, mut application: A) -> Result<()> {
+        let sdl_context = self.sdl_context.take().ok_or(Error::NoEventLoop)?;
+        let mut event_pump = sdl_context.event_pump().map_err(Error::Sdl)?;
+        let mouse = sdl_context.mouse();
+
+        // Gamepads are optional, so failing to initialize them only costs gamepad input
+        let controller_subsystem = sdl_context.game_controller()
+            .map_err(|err| log::warn!("Gamepads are unavailable: {err}"))
+            .ok();
+        // Keyed by joystick instance id; SDL sends an added event for every gamepad already plugged in
+        let mut controllers = HashMap::new();
+
+        application.setup(&mut self.renderer)?;
+        if self.auto_frame {
+            self.frame_scene();
+        }
+        if self.autoplay_camera_path {
+            self.play_camera_path();
+        }
+        // Whatever setup did is the starting state, not something to interpolate towards
+        self.renderer.begin_step();
+
+        let mut last_instant = Instant::now();
+        let mut running = true;
+        let mut minimized = false;
+        while running {
+            // Measured across the whole frame, including the time spent waiting for the limiter
+            let now = Instant::now();
+            let frame_time = now - last_instant;
+            last_instant = now;
+
+            for event in event_pump.poll_iter() {
+                match event {
+                    Event::ControllerDeviceAdded { which, .. } => {
+                        if let Some(controller_subsystem) = &controller_subsystem {
+                            match controller_subsystem.open(which) {
+                                Ok(controller) => {
+                                    log::info!("Gamepad connected: {}", controller.name());
+                                    controllers.insert(controller.instance_id(), controller);
+                                },
+                                Err(err) => log::warn!("Failed to open gamepad {which}: {err}")
+                            }
+                        }
+                    },
+                    Event::ControllerDeviceRemoved { which, .. } => {
+                        if let Some(controller) = controllers.remove(&which) {
+                            log::info!("Gamepad disconnected: {}", controller.name());
+                        }
+                    },
+                    _ => {}
+                }
+
+                if application.event(&mut self.renderer, &event) {
+                    self.input.process_release(&event);
+                    continue;
+                }
+                self.input.process_event(&event);
+
+                match event {
+                    Event::Window { win_event: WindowEvent::Minimized, .. } => { minimized = true; },
+
+                    // The sizes in these events are in points, so the drawable size is queried instead
+                    Event::Window {
+                        win_event: WindowEvent::Resized(..) | WindowEvent::SizeChanged(..) | WindowEvent::Maximized | WindowEvent::Restored,
+                        ..
+                    } => {
+                        minimized = false;
+                        self.renderer.resize_to_window();
+                    },
+
+                    Event::Quit {..} => {
+                        running = false;
+                        break;
+                    },
+
+                    _ if self.input.action_pressed(input::QUIT, &event) => {
+                        running = false;
+                        break;
+                    },
+
+                    _ => self.process_actions(&event)
+                }
+            }
+
+            // Relative mode hides the cursor and keeps reporting motion at the window's edges
+            if mouse.relative_mouse_mode() != self.input.mouse_look() {
+                mouse.set_relative_mouse_mode(self.input.mouse_look());
+            }
+
+            // The camera already moves every frame, so only what the application does to it in its
+            // steps is interpolated
+            self.update_camera(frame_time);
+            self.renderer.snap_camera();
+
+            let step = self.timestep.step();
+            for _ in 0..self.timestep.advance(frame_time) {
+                self.renderer.begin_step();
+                application.update(&mut self.renderer, step);
+            }
+            self.renderer.set_interpolation_alpha(self.timestep.alpha());
+
+            // Nothing is visible while minimized, so rendering pauses but the simulation keeps going
+            if minimized {
+                thread::sleep(Self::MINIMIZED_FRAME_TIME);
+                continue;
+            }
+
+            application.render(&mut self.renderer);
+            match self.renderer.render() {
+                Ok(()) => {},
+                Err(Error::Surface(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated)) => self.renderer.reconfigure_surface(),
+                Err(Error::Surface(wgpu::SurfaceError::Timeout)) => log::warn!("Timed out acquiring a frame, skipping it"),
+                // Out of memory
+                Err(err) => return Err(err)
+            }
+
+            self.frame_limiter.wait();
+        }
+
+        Ok(())
+    }
+
+    pub fn run_headless<A: Application, P: AsRef<Path>>(mut self, mut application: A, output: P) -> Result<()> {
+        application.setup(&mut self.renderer)?;
+        if self.auto_frame {
+            self.frame_scene();
+        }
+        application.update(&mut self.renderer, Duration::ZERO);
+
+        application.render(&mut self.renderer);
+        self.renderer.render()?;
+        self.renderer.save_frame(output)
+    }
+
+    // Renders the camera path at fps frames per second of path time into numbered PNGs in
+    // output_dir, from frame_00000.png, with the application updated once per frame
+    pub fn run_headless_camera_path<A: Application, P: AsRef<Path>>(mut self, mut application: A, output_dir: P, fps: u32) -> Result<()> {
+        if self.camera_path.is_empty() {
+            return Err(Error::EmptyCameraPath);
+        }
+        let output_dir = output_dir.as_ref();
+        fs::create_dir_all(output_dir).map_err(|source| Error::Io { path: output_dir.to_path_buf(), source })?;
+
+        application.setup(&mut self.renderer)?;
+
+        let fps = fps.max(1);
+        let step = Duration::from_secs(1) / fps;
+        let frames = (self.camera_path.duration() * fps as f32).floor() as u32 + 1;
+        for frame in 0..frames {
+            // Each time is computed from the frame number, so rounding in the step does not build up
+            self.renderer.begin_step();
+            if let Some(camera) = self.camera_path.sample(frame as f32 / fps as f32) {
+                *self.renderer.camera_mut() = camera;
+            }
+            application.update(&mut self.renderer, if frame == 0 { Duration::ZERO } else { step });
+
+            application.render(&mut self.renderer);
+            self.renderer.render()?;
+            self.renderer.save_frame(output_dir.join(format!("frame_{frame:05}.png")))?;
+        }
+
+        Ok(())
+    }
+}
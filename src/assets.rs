@@ -0,0 +1,297 @@
+use crate::{
+    model,
+    texture
+};
+use crate::vfs::AssetPath;
+use crate::error::Result;
+
+use std::{
+    collections::HashMap,
+    fmt,
+    hash::{
+        Hash,
+        Hasher
+    },
+    marker::PhantomData,
+    sync::{
+        mpsc,
+        Arc,
+        Weak
+    }
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AssetId(u64);
+
+// What a model load does when one of its textures or its MTL file cannot be read
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MissingAssetPolicy {
+    // Log a warning and substitute the missing texture, keeping the rest of the model
+    #[default]
+    Fallback,
+    // Fail the whole model load
+    Error
+}
+
+struct HandleInner {
+    id: AssetId,
+    drop_sender: mpsc::Sender<AssetId>
+}
+
+impl Drop for HandleInner {
+    fn drop(&mut self) {
+        // The receiver is gone once the asset storage itself has been dropped
+        let _ = self.drop_sender.send(self.id);
+    }
+}
+
+// Keeps its asset alive; the asset is freed on the next collect_garbage after the last clone drops
+pub struct Handle<T> {
+    inner: Arc<HandleInner>,
+    _marker: PhantomData<fn() -> T>
+}
+
+impl<T> Handle<T> {
+    pub fn id(&self) -> AssetId {
+        self.inner.id
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+            _marker: PhantomData
+        }
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id() == other.id()
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id().hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Handle").field(&self.id()).finish()
+    }
+}
+
+struct AssetEntry<T> {
+    asset: T,
+    path: Option<AssetPath>
+}
+
+pub struct Assets<T> {
+    entries: HashMap<AssetId, AssetEntry<T>>,
+    by_path: HashMap<AssetPath, Weak<HandleInner>>,
+    next_id: u64,
+    drop_sender: mpsc::Sender<AssetId>,
+    drop_receiver: mpsc::Receiver<AssetId>
+}
+
+impl<T> Assets<T> {
+    pub fn new() -> Self {
+        let (drop_sender, drop_receiver) = mpsc::channel();
+        Self {
+            entries: HashMap::new(),
+            by_path: HashMap::new(),
+            next_id: 0,
+            drop_sender,
+            drop_receiver
+        }
+    }
+
+    fn insert(&mut self, asset: T, path: Option<AssetPath>) -> Handle<T> {
+        let id = AssetId(self.next_id);
+        self.next_id += 1;
+
+        let inner = Arc::new(HandleInner {
+            id,
+            drop_sender: self.drop_sender.clone()
+        });
+        if let Some(path) = &path {
+            self.by_path.insert(path.clone(), Arc::downgrade(&inner));
+        }
+        self.entries.insert(id, AssetEntry { asset, path });
+
+        Handle {
+            inner,
+            _marker: PhantomData
+        }
+    }
+
+    pub fn add(&mut self, asset: T) -> Handle<T> {
+        self.insert(asset, None)
+    }
+
+    // Assets are cached by resolved path, so loading the same file twice shares one handle
+    pub fn get_or_load<F>(&mut self, path: AssetPath, load: F) -> Result<Handle<T>>
+    where
+        F: FnOnce(&AssetPath) -> Result<T>
+    {
+        if let Some(inner) = self.by_path.get(&path).and_then(Weak::upgrade) {
+            return Ok(Handle {
+                inner,
+                _marker: PhantomData
+            });
+        }
+
+        let asset = load(&path)?;
+        Ok(self.insert(asset, Some(path)))
+    }
+
+    pub fn get(&self, handle: &Handle<T>) -> &T {
+        // A live handle keeps its entry from being collected
+        &self.entries[&handle.id()].asset
+    }
+
+    pub(crate) fn get_by_id(&self, id: AssetId) -> Option<&T> {
+        self.entries.get(&id).map(|entry| &entry.asset)
+    }
+
+    pub fn path(&self, handle: &Handle<T>) -> Option<&AssetPath> {
+        self.entries[&handle.id()].path.as_ref()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn collect_garbage(&mut self) -> Vec<AssetId> {
+        let dropped: Vec<AssetId> = self.drop_receiver.try_iter().collect();
+        for id in &dropped {
+            if let Some(AssetEntry { path: Some(path), .. }) = self.entries.remove(id) {
+                // The path may already point at a newer handle if the file was loaded again
+                if self.by_path.get(&path).is_some_and(|inner| inner.strong_count() == 0) {
+                    self.by_path.remove(&path);
+                }
+            }
+        }
+        dropped
+    }
+}
+
+impl<T> Default for Assets<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct AssetServer {
+    pub models: Assets<model::Model>,
+    pub textures: Assets<texture::Texture>
+}
+
+impl AssetServer {
+    pub fn new() -> Self {
+        Self {
+            models: Assets::new(),
+            textures: Assets::new()
+        }
+    }
+
+    pub fn collect_garbage(&mut self) -> Vec<AssetId> {
+        // Models go first since dropping them releases their material textures
+        let dropped_models = self.models.collect_garbage();
+        self.textures.collect_garbage();
+        dropped_models
+    }
+}
+
+impl Default for AssetServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    use std::path::PathBuf;
+
+    fn path(name: &str) -> AssetPath {
+        AssetPath::File(PathBuf::from(name))
+    }
+
+    #[test]
+    fn assets_are_freed_after_the_last_handle_drops() {
+        let mut assets = Assets::new();
+        let handle = assets.add(1);
+        let clone = handle.clone();
+
+        drop(handle);
+        assert!(assets.collect_garbage().is_empty());
+        assert_eq!(*assets.get(&clone), 1);
+
+        let id = clone.id();
+        drop(clone);
+        assert_eq!(assets.collect_garbage(), [id]);
+        assert!(assets.is_empty());
+        assert!(assets.get_by_id(id).is_none());
+    }
+
+    #[test]
+    fn loading_a_path_twice_shares_one_asset() {
+        let mut assets = Assets::new();
+        let first = assets.get_or_load(path("a"), |_| Ok(1)).unwrap();
+        let second = assets.get_or_load(path("a"), |_| panic!("loaded twice")).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(assets.len(), 1);
+        assert_eq!(assets.path(&first), Some(&path("a")));
+
+        let other = assets.get_or_load(path("b"), |_| Ok(2)).unwrap();
+        assert_ne!(first, other);
+        assert_eq!(assets.len(), 2);
+    }
+
+    #[test]
+    fn a_freed_path_loads_again() {
+        let mut assets = Assets::new();
+        let first = assets.get_or_load(path("a"), |_| Ok(1)).unwrap();
+        let first_id = first.id();
+        drop(first);
+        assets.collect_garbage();
+
+        let second = assets.get_or_load(path("a"), |_| Ok(2)).unwrap();
+        assert_ne!(second.id(), first_id);
+        assert_eq!(*assets.get(&second), 2);
+    }
+
+    #[test]
+    fn collecting_an_old_handle_keeps_a_reload_cached() {
+        let mut assets = Assets::new();
+        let first = assets.get_or_load(path("a"), |_| Ok(1)).unwrap();
+        // Dropped but not yet collected, so the reload is a fresh asset under the same path
+        drop(first);
+        let second = assets.get_or_load(path("a"), |_| Ok(2)).unwrap();
+        assets.collect_garbage();
+
+        let third = assets.get_or_load(path("a"), |_| panic!("cache entry lost")).unwrap();
+        assert_eq!(third, second);
+        assert_eq!(assets.len(), 1);
+    }
+
+    #[test]
+    fn failed_loads_are_not_cached() {
+        let mut assets: Assets<i32> = Assets::new();
+        assert!(assets.get_or_load(path("a"), |_| Err(Error::ReadbackUnsupported)).is_err());
+        assert!(assets.is_empty());
+        assert!(assets.get_or_load(path("a"), |_| Ok(1)).is_ok());
+    }
+}
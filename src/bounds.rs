@@ -0,0 +1,346 @@
+use cgmath::{
+    EuclideanSpace,
+    InnerSpace,
+    Matrix,
+    Matrix4,
+    Point3,
+    Transform as _,
+    Vector3,
+    Vector4
+};
+
+// Axis-aligned bounding box
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>
+}
+
+impl Aabb {
+    pub fn new<P: Into<Point3<f32>>>(min: P, max: P) -> Self {
+        Self {
+            min: min.into(),
+            max: max.into()
+        }
+    }
+
+    // None when there are no points
+    pub fn from_points<I: IntoIterator<Item = Point3<f32>>>(points: I) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(Self::new(first, first), |aabb, point| aabb.union(&Self::new(point, point))))
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self {
+            min: Point3::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y), self.min.z.min(other.min.z)),
+            max: Point3::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y), self.max.z.max(other.max.z))
+        }
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    // Half the diagonal, so a sphere at the center with this radius contains the box
+    pub fn radius(&self) -> f32 {
+        (self.max - self.min).magnitude() / 2.0
+    }
+
+    pub fn corners(&self) -> [Point3<f32>; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            Point3::new(min.x, min.y, min.z),
+            Point3::new(max.x, min.y, min.z),
+            Point3::new(min.x, max.y, min.z),
+            Point3::new(max.x, max.y, min.z),
+            Point3::new(min.x, min.y, max.z),
+            Point3::new(max.x, min.y, max.z),
+            Point3::new(min.x, max.y, max.z),
+            Point3::new(max.x, max.y, max.z)
+        ]
+    }
+
+    // The box around all eight transformed corners, which still contains everything it did
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Self {
+        let [first, rest @ ..] = self.corners().map(|corner| matrix.transform_point(corner));
+        rest.iter().fold(Self::new(first, first), |aabb, &corner| aabb.union(&Self::new(corner, corner)))
+    }
+}
+
+// Cheaper than a box to test against, and stays tight under rotation
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Point3<f32>,
+    pub radius: f32
+}
+
+impl BoundingSphere {
+    pub fn new<P: Into<Point3<f32>>>(center: P, radius: f32) -> Self {
+        Self {
+            center: center.into(),
+            radius
+        }
+    }
+
+    // Centered on the points' bounding box and just large enough to hold them all, which is not
+    // the smallest sphere but usually close. None when there are no points
+    pub fn from_points<I>(points: I) -> Option<Self>
+    where
+        I: IntoIterator<Item = Point3<f32>>,
+        I::IntoIter: Clone
+    {
+        let points = points.into_iter();
+        let center = Aabb::from_points(points.clone())?.center();
+        let radius = points.fold(0.0f32, |radius, point| radius.max((point - center).magnitude()));
+        Some(Self::new(center, radius))
+    }
+
+    // The smallest sphere containing both
+    pub fn union(&self, other: &BoundingSphere) -> Self {
+        let offset = other.center - self.center;
+        let distance = offset.magnitude();
+        if distance + other.radius <= self.radius {
+            return *self;
+        }
+        if distance + self.radius <= other.radius {
+            return *other;
+        }
+        let radius = (distance + self.radius + other.radius) / 2.0;
+        Self::new(self.center + offset * ((radius - self.radius) / distance), radius)
+    }
+
+    // Scaled by the largest axis scale, so non-uniform scaling gives a sphere that is looser than it
+    // could be but still contains everything it did
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Self {
+        let scale = [matrix.x, matrix.y, matrix.z]
+            .iter()
+            .map(|axis| axis.truncate().magnitude())
+            .fold(0.0f32, f32::max);
+        Self::new(matrix.transform_point(self.center), self.radius * scale)
+    }
+}
+
+// A half-line, with a unit direction
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    pub direction: Vector3<f32>
+}
+
+impl Ray {
+    pub fn new<P: Into<Point3<f32>>, V: Into<Vector3<f32>>>(origin: P, direction: V) -> Self {
+        Self {
+            origin: origin.into(),
+            direction: direction.into().normalize()
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> Point3<f32> {
+        self.origin + self.direction * distance
+    }
+
+    // Distance along the ray to where it enters the box, 0 if it starts inside, or None if it misses
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let mut near = 0.0f32;
+        let mut far = f32::INFINITY;
+        for axis in 0..3 {
+            // Division by a zero component gives infinities, which the comparisons handle
+            let inverse = 1.0 / self.direction[axis];
+            let a = (aabb.min[axis] - self.origin[axis]) * inverse;
+            let b = (aabb.max[axis] - self.origin[axis]) * inverse;
+            near = near.max(a.min(b));
+            far = far.min(a.max(b));
+        }
+        (near <= far).then_some(near)
+    }
+
+    // Distance along the ray to where it enters the sphere, 0 if it starts inside, or None if it misses
+    pub fn intersect_sphere(&self, sphere: &BoundingSphere) -> Option<f32> {
+        let offset = self.origin - sphere.center;
+        let b = offset.dot(self.direction);
+        let c = offset.magnitude2() - sphere.radius * sphere.radius;
+        // Outside and pointing away
+        if c > 0.0 && b > 0.0 {
+            return None;
+        }
+        let discriminant = b * b - c;
+        (discriminant >= 0.0).then(|| (-b - discriminant.sqrt()).max(0.0))
+    }
+}
+
+// The six planes bounding what a camera can see, each pointing inwards
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Frustum {
+    planes: [Vector4<f32>; 6]
+}
+
+impl Frustum {
+    // Extracts the planes from an OpenGL-style view-projection matrix, with clip z from -w to w
+    pub fn from_matrix(view_proj: &Matrix4<f32>) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|row| view_proj.row(row));
+        Self {
+            planes: [w + x, w - x, w + y, w - y, w + z, w - z]
+        }
+    }
+
+    // Conservative like the box test, but cheaper
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes.iter().all(|plane| {
+            // The planes are not normalized, so the radius is scaled to match
+            let normal = plane.truncate();
+            normal.dot(sphere.center.to_vec()) + plane.w >= -sphere.radius * normal.magnitude()
+        })
+    }
+
+    // Conservative: boxes near a corner of the frustum can pass without being visible
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // The corner furthest along the plane's normal
+            let corner = Vector4::new(
+                if plane.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if plane.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if plane.z >= 0.0 { aabb.max.z } else { aabb.min.z },
+                1.0
+            );
+            plane.dot(corner) >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cgmath::{
+        Deg,
+        SquareMatrix
+    };
+
+    fn assert_point_eq(actual: Point3<f32>, expected: Point3<f32>) {
+        assert!((actual - expected).magnitude() < 1e-5, "{actual:?} != {expected:?}");
+    }
+
+    fn assert_close(actual: Option<f32>, expected: f32) {
+        assert!(actual.is_some_and(|actual| (actual - expected).abs() < 1e-5), "{actual:?} != {expected}");
+    }
+
+    #[test]
+    fn boxes_cover_their_points() {
+        let aabb = Aabb::from_points([[1.0, -2.0, 0.0], [-1.0, 3.0, 0.5], [0.0, 0.0, -4.0]].map(Point3::from)).unwrap();
+        assert_eq!(aabb, Aabb::new([-1.0, -2.0, -4.0], [1.0, 3.0, 0.5]));
+        assert_eq!(Aabb::from_points([]), None);
+    }
+
+    #[test]
+    fn transformed_boxes_are_scaled_moved_and_grown_by_rotation() {
+        let aabb = Aabb::new([-1.0, -1.0, -1.0], [1.0, 1.0, 1.0]);
+
+        let scaled = aabb.transform(&(Matrix4::from_translation(Vector3::new(5.0, 0.0, 0.0)) * Matrix4::from_nonuniform_scale(2.0, 1.0, 3.0)));
+        assert_point_eq(scaled.min, Point3::new(3.0, -1.0, -3.0));
+        assert_point_eq(scaled.max, Point3::new(7.0, 1.0, 3.0));
+
+        // Turned by 45 degrees, the corners stick out along the x and y axes
+        let rotated = aabb.transform(&Matrix4::from_angle_z(Deg(45.0)));
+        let half_diagonal = 2.0f32.sqrt();
+        assert_point_eq(rotated.min, Point3::new(-half_diagonal, -half_diagonal, -1.0));
+        assert_point_eq(rotated.max, Point3::new(half_diagonal, half_diagonal, 1.0));
+    }
+
+    #[test]
+    fn spheres_cover_their_points() {
+        let sphere = BoundingSphere::from_points([[1.0, 0.0, 0.0], [-1.0, 0.0, 0.0], [0.0, 2.0, 0.0]].map(Point3::from)).unwrap();
+        assert_point_eq(sphere.center, Point3::new(0.0, 1.0, 0.0));
+        assert!((sphere.radius - 2.0f32.sqrt()).abs() < 1e-5);
+        assert_eq!(BoundingSphere::from_points([]), None);
+    }
+
+    #[test]
+    fn transformed_spheres_use_the_largest_scale() {
+        let sphere = BoundingSphere::new([1.0, 0.0, 0.0], 2.0);
+        let transformed = sphere.transform(&(Matrix4::from_translation(Vector3::new(0.0, 0.0, 4.0)) * Matrix4::from_nonuniform_scale(1.0, 3.0, 0.5)));
+        assert_point_eq(transformed.center, Point3::new(1.0, 0.0, 4.0));
+        assert!((transformed.radius - 6.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn sphere_unions_contain_both_spheres() {
+        let small = BoundingSphere::new([0.0, 0.0, 0.0], 1.0);
+        let large = BoundingSphere::new([4.0, 0.0, 0.0], 3.0);
+        let union = small.union(&large);
+        assert_point_eq(union.center, Point3::new(3.0, 0.0, 0.0));
+        assert!((union.radius - 4.0).abs() < 1e-5);
+
+        // One already inside the other
+        assert_eq!(large.union(&BoundingSphere::new([4.5, 0.0, 0.0], 1.0)), large);
+        assert_eq!(small.union(&large.union(&small)), large.union(&small));
+    }
+
+    #[test]
+    fn rays_hit_and_miss_boxes() {
+        let aabb = Aabb::new([-1.0, -1.0, -1.0], [1.0, 1.0, 1.0]);
+        assert_close(Ray::new([0.0, 0.0, -5.0], [0.0, 0.0, 1.0]).intersect_aabb(&aabb), 4.0);
+        assert_close(Ray::new([-3.0, -3.0, 0.0], [1.0, 1.0, 0.0]).intersect_aabb(&aabb), 2.0 * 2.0f32.sqrt());
+        assert_eq!(Ray::new([0.0, 0.0, -5.0], [0.0, 0.0, -1.0]).intersect_aabb(&aabb), None);
+        // Parallel to a face, outside the slab
+        assert_eq!(Ray::new([0.0, 2.0, -5.0], [0.0, 0.0, 1.0]).intersect_aabb(&aabb), None);
+        assert_close(Ray::new([0.5, 0.0, 0.0], [0.0, 1.0, 0.0]).intersect_aabb(&aabb), 0.0);
+    }
+
+    #[test]
+    fn rays_hit_and_miss_spheres() {
+        let sphere = BoundingSphere::new([0.0, 0.0, 0.0], 1.0);
+        assert_close(Ray::new([0.0, 0.0, -5.0], [0.0, 0.0, 1.0]).intersect_sphere(&sphere), 4.0);
+        assert_eq!(Ray::new([0.0, 0.0, -5.0], [0.0, 0.0, -1.0]).intersect_sphere(&sphere), None);
+        assert_eq!(Ray::new([0.0, 2.0, -5.0], [0.0, 0.0, 1.0]).intersect_sphere(&sphere), None);
+        assert_close(Ray::new([0.5, 0.0, 0.0], [0.0, 1.0, 0.0]).intersect_sphere(&sphere), 0.0);
+    }
+
+    // For each plane in turn (left, right, bottom, top, near, far), a unit sphere past it and one
+    // crossing it, both checked alongside one well inside
+    fn assert_spheres_culled(frustum: &Frustum, inside: [f32; 3], outside_and_straddling: [([f32; 3], [f32; 3]); 6]) {
+        assert!(frustum.intersects_sphere(&BoundingSphere::new(inside, 1.0)));
+        for (plane, (outside, straddling)) in outside_and_straddling.into_iter().enumerate() {
+            assert!(!frustum.intersects_sphere(&BoundingSphere::new(outside, 1.0)), "plane {plane}: {outside:?} should be outside");
+            assert!(frustum.intersects_sphere(&BoundingSphere::new(straddling, 1.0)), "plane {plane}: {straddling:?} should intersect");
+        }
+    }
+
+    #[test]
+    fn perspective_frustums_cull_spheres_against_every_plane() {
+        // Looking down -z with a 90 degree field of view, so the sides are at 45 degrees
+        let frustum = Frustum::from_matrix(&cgmath::perspective(Deg(90.0), 1.0, 1.0, 100.0));
+        assert_spheres_culled(&frustum, [0.0, 0.0, -10.0], [
+            ([-12.0, 0.0, -10.0], [-10.5, 0.0, -10.0]),
+            ([12.0, 0.0, -10.0], [10.5, 0.0, -10.0]),
+            ([0.0, -12.0, -10.0], [0.0, -10.5, -10.0]),
+            ([0.0, 12.0, -10.0], [0.0, 10.5, -10.0]),
+            ([0.0, 0.0, 0.5], [0.0, 0.0, -0.5]),
+            ([0.0, 0.0, -101.5], [0.0, 0.0, -100.5])
+        ]);
+    }
+
+    #[test]
+    fn orthographic_frustums_cull_spheres_against_every_plane() {
+        let frustum = Frustum::from_matrix(&cgmath::ortho(-2.0, 2.0, -3.0, 3.0, 1.0, 100.0));
+        assert_spheres_culled(&frustum, [0.0, 0.0, -50.0], [
+            ([-3.5, 0.0, -50.0], [-2.5, 0.0, -50.0]),
+            ([3.5, 0.0, -50.0], [2.5, 0.0, -50.0]),
+            ([0.0, -4.5, -50.0], [0.0, -3.5, -50.0]),
+            ([0.0, 4.5, -50.0], [0.0, 3.5, -50.0]),
+            ([0.0, 0.0, 0.5], [0.0, 0.0, -0.5]),
+            ([0.0, 0.0, -101.5], [0.0, 0.0, -100.5])
+        ]);
+    }
+
+    #[test]
+    fn frustum_planes_follow_the_view() {
+        // The same frustum turned to look down -x instead
+        let view = Matrix4::from_angle_y(Deg(90.0)).invert().unwrap();
+        let frustum = Frustum::from_matrix(&(cgmath::perspective(Deg(90.0), 1.0, 1.0, 100.0) * view));
+        assert!(frustum.intersects_sphere(&BoundingSphere::new([-10.0, 0.0, 0.0], 1.0)));
+        assert!(!frustum.intersects_sphere(&BoundingSphere::new([10.0, 0.0, 0.0], 1.0)));
+        assert!(frustum.intersects_aabb(&Aabb::new([-11.0, -1.0, -1.0], [-9.0, 1.0, 1.0])));
+        assert!(!frustum.intersects_aabb(&Aabb::new([9.0, -1.0, -1.0], [11.0, 1.0, 1.0])));
+    }
+}
@@ -1,20 +1,29 @@
 use wgpu::util::DeviceExt;
 use cgmath::{
     Angle,
-    InnerSpace
+    EuclideanSpace,
+    InnerSpace,
+    Rotation3
 };
 use cgmath::{
+    Matrix3,
     Matrix4,
+    Quaternion,
+    SquareMatrix,
     Vector3,
+    Vector4,
     Point3,
     Rad,
     Deg
 };
-use sdl2::{
-    event::Event,
-    keyboard::Keycode
+use crate::bounds::{
+    Frustum,
+    Ray
+};
+use serde::{
+    Deserialize,
+    Serialize
 };
-use std::time::Duration;
 
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
@@ -42,12 +51,21 @@ impl CameraProjectionRaw {
     }
 }
 
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProjectionMode {
+    #[default]
+    Perspective,
+    // Parallel lines stay parallel. The view is as tall as the perspective one at the camera's
+    // focus distance, so switching modes keeps whatever is in focus the same size
+    Orthographic
+}
+
+#[derive(Copy, Clone, Debug)]
 pub struct CameraProjection {
-    fovy: Rad<f32>,
+    mode: ProjectionMode,
     aspect: f32,
     near: f32,
-    far: f32,
-    up: Vector3<f32>
+    far: f32
 }
 
 impl CameraProjection {
@@ -58,16 +76,13 @@ impl CameraProjection {
         0.0, 0.0, 0.0, 1.0
     );
 
-    pub fn new<F>(fovy: F, container_width: f32, container_height: f32, near: f32, far: f32) -> Self
-    where
-        F: Into<Rad<f32>>,
-    {
+    // The field of view comes from the camera, so it can change and be interpolated with it
+    pub fn new(container_width: f32, container_height: f32, near: f32, far: f32) -> Self {
         Self {
-            fovy: fovy.into(),
+            mode: ProjectionMode::default(),
             aspect: container_width / container_height,
             near,
-            far,
-            up: Vector3::unit_y()
+            far
         }
     }
 
@@ -75,121 +90,405 @@ impl CameraProjection {
         self.aspect = width / height;
     }
 
+    pub fn mode(&self) -> ProjectionMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: ProjectionMode) {
+        self.mode = mode;
+    }
+
+    // In OpenGL's clip space, with z from -w to w, which is what frustum and ray maths expect
+    pub fn view_proj_matrix(&self, camera: &Camera) -> Matrix4<f32> {
+        let proj = match self.mode {
+            ProjectionMode::Perspective => cgmath::perspective(camera.fovy, self.aspect, self.near, self.far),
+            ProjectionMode::Orthographic => {
+                let half_height = camera.focus_distance * Rad::tan(camera.fovy / 2.0);
+                let half_width = half_height * self.aspect;
+                cgmath::ortho(-half_width, half_width, -half_height, half_height, self.near, self.far)
+            }
+        };
+        proj * camera.view_matrix()
+    }
+
+    pub fn frustum(&self, camera: &Camera) -> Frustum {
+        Frustum::from_matrix(&self.view_proj_matrix(camera))
+    }
+
+    // The ray through a point on screen, given in normalized device coordinates (-1 to 1, y up).
+    // Perspective rays all start at the camera, orthographic ones are parallel
+    pub fn ray(&self, camera: &Camera, x: f32, y: f32) -> Option<Ray> {
+        let inverse = self.view_proj_matrix(camera).invert()?;
+        let unproject = |z| {
+            let point = inverse * Vector4::new(x, y, z, 1.0);
+            Point3::from_homogeneous(point)
+        };
+        let near = unproject(-1.0);
+        Some(Ray::new(near, unproject(1.0) - near))
+    }
+
+    // The ray through a pixel of a width by height target, counted from its top left corner
+    pub fn pixel_ray(&self, camera: &Camera, x: f32, y: f32, width: u32, height: u32) -> Option<Ray> {
+        self.ray(camera, 2.0 * x / width as f32 - 1.0, 1.0 - 2.0 * y / height as f32)
+    }
+
     fn build_proj_matrix(&self, camera: &Camera) -> Matrix4<f32> {
-        Self::OPENGL_TO_WGPU_MATRIX *
-        cgmath::perspective(self.fovy, self.aspect, self.near, self.far) *
-        Matrix4::look_to_rh(camera.position, camera.calc_dir_vector(), self.up)
+        Self::OPENGL_TO_WGPU_MATRIX * self.view_proj_matrix(camera)
     }
 }
 
+// Looks along its local -z axis with local +y up, like an OpenGL camera
+#[derive(Copy, Clone, Debug)]
 pub struct Camera {
     pub position: Point3<f32>,
-    pub yaw: Rad<f32>,
-    pub pitch: Rad<f32>
+    pub orientation: Quaternion<f32>,
+    // Vertical field of view
+    pub fovy: Rad<f32>,
+    // How far ahead the camera's subject is: what the orbit controller circles, what framing fits
+    // and what sets the orthographic view size
+    pub focus_distance: f32
+}
+
+// Axis-aligned views for orthographic reviews
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum View {
+    // Looking along +z
+    Front,
+    // Looking along -x
+    Side,
+    // Looking down along -y, with +z at the top of the screen
+    Top
 }
 
 impl Camera {
+    pub const DEFAULT_FOVY: Deg<f32> = Deg(45.0);
+    // Just short of straight up or down, so the fly controls keep a horizontal forward direction
+    pub const MAX_PITCH: Deg<f32> = Deg(90.0 - 0.01);
+
+    // Yaw turns from +x towards +z (so 90 degrees looks along +z) and pitch tilts upwards
     pub fn new<V, Y, P>(position: V, yaw: Y, pitch: P) -> Self
     where
         V: Into<Point3<f32>>,
         Y: Into<Rad<f32>>,
         P: Into<Rad<f32>>
     {
+        // Turning about +y by -(yaw + 90 degrees) takes -z to (cos yaw, 0, sin yaw)
+        let yaw = Quaternion::from_angle_y(-(yaw.into() + Rad::turn_div_4()));
+        let pitch = Quaternion::from_angle_x(pitch.into());
+        Self::from_orientation(position, yaw * pitch)
+    }
+
+    pub fn from_orientation<V: Into<Point3<f32>>>(position: V, orientation: Quaternion<f32>) -> Self {
         Self {
             position: position.into(),
-            yaw: yaw.into(),
-            pitch: pitch.into()
+            orientation: orientation.normalize(),
+            fovy: Self::DEFAULT_FOVY.into(),
+            focus_distance: 5.0
         }
     }
 
-    fn calc_dir_vector(&self) -> Vector3<f32> {
-        Vector3::new(self.yaw.cos(), self.pitch.tan(), self.yaw.sin())
-        // Vector3::new(self.yaw.cos() * self.pitch.cos(), self.pitch.sin(), self.yaw.sin() * self.pitch.cos())
+    // A camera at position looking at target, with up pointing as close to up as possible
+    pub fn looking_at<V, T>(position: V, target: T, up: Vector3<f32>) -> Self
+    where
+        V: Into<Point3<f32>>,
+        T: Into<Point3<f32>>
+    {
+        let position = position.into();
+        Self::from_orientation(position, Self::look_to_orientation(target.into() - position, up))
     }
 
-    pub fn dirs_forward_right(&self) -> (Vector3<f32>, Vector3<f32>) {
-        let (yaw_sin, yaw_cos) = self.yaw.sin_cos();
-        (Vector3::new(yaw_cos, 0.0, yaw_sin),
-        Vector3::new(-yaw_sin, 0.0, yaw_cos))
+    pub fn with_fovy<F: Into<Rad<f32>>>(mut self, fovy: F) -> Self {
+        self.fovy = fovy.into();
+        self
     }
-}
 
-pub struct CameraController {
-    delta_forward: f32,
-    delta_right: f32,
-    delta_up: f32,
-    delta_yaw: f32,
-    delta_pitch: f32,
-    move_speed: f32,
-    rot_speed: f32
-}
+    // Positive roll tilts the top of the camera to the right
+    pub fn with_roll<R: Into<Rad<f32>>>(mut self, roll: R) -> Self {
+        self.rotate_roll(roll);
+        self
+    }
 
-impl CameraController {
-    pub fn new(move_speed: f32, rot_speed: f32) -> Self {
+    // Positions are interpolated linearly and orientations along the shortest arc
+    pub fn lerp(&self, other: &Camera, amount: f32) -> Self {
         Self {
-            delta_forward: 0.0,
-            delta_right: 0.0,
-            delta_up: 0.0,
-            delta_yaw: 0.0,
-            delta_pitch: 0.0,
-            move_speed,
-            rot_speed
+            position: self.position + (other.position - self.position) * amount,
+            orientation: self.orientation.slerp(other.orientation, amount),
+            fovy: self.fovy + (other.fovy - self.fovy) * amount,
+            focus_distance: self.focus_distance + (other.focus_distance - self.focus_distance) * amount
         }
     }
 
-    pub fn process_event(&mut self, event: Event) {
-        match event {
-            Event::KeyDown { keycode: Some(keycode), .. } => match keycode {
-                Keycode::W => { self.delta_forward = self.move_speed },
-                Keycode::S => { self.delta_forward = -self.move_speed },
-                Keycode::D => { self.delta_right = self.move_speed },
-                Keycode::A => { self.delta_right = -self.move_speed },
+    // Unit vector the camera looks along
+    pub fn forward(&self) -> Vector3<f32> {
+        self.orientation * -Vector3::unit_z()
+    }
+
+    pub fn right(&self) -> Vector3<f32> {
+        self.orientation * Vector3::unit_x()
+    }
+
+    pub fn up(&self) -> Vector3<f32> {
+        self.orientation * Vector3::unit_y()
+    }
+
+    // Heading of the forward direction, measured like the yaw given to new
+    pub fn yaw(&self) -> Rad<f32> {
+        let forward = self.forward();
+        Rad::atan2(forward.z, forward.x)
+    }
 
-                Keycode::Space => { self.delta_up = self.move_speed },
-                Keycode::LShift | Keycode::RShift => { self.delta_up = -self.move_speed },
+    // Measured with atan2 rather than asin, which loses all precision close to straight up
+    pub fn pitch(&self) -> Rad<f32> {
+        let forward = self.forward();
+        Rad::atan2(forward.y, forward.x.hypot(forward.z))
+    }
 
-                Keycode::Right => { self.delta_yaw = self.rot_speed },
-                Keycode::Left => { self.delta_yaw = -self.rot_speed },
-                Keycode::Up => { self.delta_pitch = self.rot_speed },
-                Keycode::Down => { self.delta_pitch = -self.rot_speed },
+    // Turns about the world's up axis, so the horizon stays level
+    pub fn rotate_yaw<A: Into<Rad<f32>>>(&mut self, angle: A) {
+        self.orientation = (Quaternion::from_angle_y(-angle.into()) * self.orientation).normalize();
+    }
 
+    // Tilts about the camera's right axis, stopping at MAX_PITCH either way
+    pub fn rotate_pitch<A: Into<Rad<f32>>>(&mut self, angle: A) {
+        let max_pitch = Rad::from(Self::MAX_PITCH).0;
+        let pitch = self.pitch().0;
+        let angle = (pitch + angle.into().0).clamp(-max_pitch, max_pitch) - pitch;
+        self.orientation = (self.orientation * Quaternion::from_angle_x(Rad(angle))).normalize();
+    }
 
-                _ => {}
-            },
+    // Turns about the view direction; positive tilts the top of the camera to the right
+    pub fn rotate_roll<A: Into<Rad<f32>>>(&mut self, angle: A) {
+        self.orientation = (self.orientation * Quaternion::from_angle_z(-angle.into())).normalize();
+    }
 
-            Event::KeyUp { keycode: Some(keycode), .. } => match keycode {
-                Keycode::W | Keycode::S => { self.delta_forward = 0.0 },
-                Keycode::D | Keycode::A => { self.delta_right = 0.0 },
+    // Turns to face target, keeping up as close to the world's up as possible
+    pub fn look_at<T: Into<Point3<f32>>>(&mut self, target: T, up: Vector3<f32>) {
+        self.orientation = Self::look_to_orientation(target.into() - self.position, up);
+    }
 
-                Keycode::Space |
-                Keycode::LShift | Keycode::RShift => { self.delta_up = 0.0 },
+    // Looking straight along up leaves no way to tell which way the top of the view should point,
+    // so it falls back to +z, as View::Top does, or +x when looking along z
+    fn look_to_orientation(direction: Vector3<f32>, up: Vector3<f32>) -> Quaternion<f32> {
+        let forward = direction.normalize();
+        let right = [up, Vector3::unit_z(), Vector3::unit_x()]
+            .into_iter()
+            .map(|up| forward.cross(up))
+            .find(|right| right.magnitude2() > 1e-6)
+            .unwrap_or_else(Vector3::unit_x)
+            .normalize();
+        let up = right.cross(forward);
+        Quaternion::from(Matrix3::from_cols(right, up, -forward)).normalize()
+    }
 
-                Keycode::Right | Keycode::Left => { self.delta_yaw = 0.0 },
-                Keycode::Up | Keycode::Down => { self.delta_pitch = 0.0 },
+    // World to view space; the inverse of the camera's own rotation and translation
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::from(self.orientation.conjugate()) * Matrix4::from_translation(-self.position.to_vec())
+    }
 
-                _ => {}
-            }
+    pub fn focus_point(&self) -> Point3<f32> {
+        self.position + self.forward() * self.focus_distance
+    }
 
-            _ => {}
+    // Turns to the view while keeping the same focus point
+    pub fn set_view(&mut self, view: View) {
+        let focus_point = self.focus_point();
+        let (direction, up) = match view {
+            View::Front => (Vector3::unit_z(), Vector3::unit_y()),
+            View::Side => (-Vector3::unit_x(), Vector3::unit_y()),
+            View::Top => (-Vector3::unit_y(), Vector3::unit_z())
+        };
+        self.orientation = Self::look_to_orientation(direction, up);
+        self.position = focus_point - self.forward() * self.focus_distance;
+    }
+
+    // How far back the camera has to be for a sphere of this radius to fit the view
+    pub fn framing_distance(&self, radius: f32, aspect: f32) -> f32 {
+        let half_fovy = self.fovy / 2.0;
+        let half_fovx = Rad::atan(half_fovy.tan() * aspect);
+        radius / Rad::sin(if half_fovx < half_fovy { half_fovx } else { half_fovy })
+    }
+
+    // The forward and right directions flattened onto the ground, for walking-style movement
+    pub fn dirs_forward_right(&self) -> (Vector3<f32>, Vector3<f32>) {
+        let (yaw_sin, yaw_cos) = self.yaw().sin_cos();
+        (Vector3::new(yaw_cos, 0.0, yaw_sin),
+        Vector3::new(-yaw_sin, 0.0, yaw_cos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bounds::Aabb;
+
+    fn assert_matrix_eq(actual: Matrix4<f32>, expected: Matrix4<f32>) {
+        let actual: &[f32; 16] = actual.as_ref();
+        let expected: &[f32; 16] = expected.as_ref();
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-4, "{actual:?} != {expected:?}");
         }
     }
 
-    pub fn update_camera(&self, camera: &mut Camera, deltatime: &Duration) {
-        let (forward, right) = camera.dirs_forward_right();
-        let forward = forward * self.move_speed;
-        let right = right * self.move_speed;
-        let deltatime = deltatime.as_secs_f32();
+    fn assert_vector_eq(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!((actual - expected).magnitude() < 1e-4, "{actual:?} != {expected:?}");
+    }
 
-        camera.position += (self.delta_forward * forward + self.delta_right * right + self.delta_up * Vector3::unit_y()) * deltatime;
+    // The direction the old yaw/pitch camera described, normalized
+    fn yaw_pitch_direction(yaw: Deg<f32>, pitch: Deg<f32>) -> Vector3<f32> {
+        let (yaw, pitch) = (Rad::from(yaw), Rad::from(pitch));
+        Vector3::new(yaw.cos() * pitch.cos(), pitch.sin(), yaw.sin() * pitch.cos())
+    }
 
-        camera.yaw.0 += self.delta_yaw * deltatime;
-        camera.pitch.0 += self.delta_pitch * deltatime;
-        // Change to use Rust's PI constants
-        if camera.pitch > Rad::from(Deg(90.0)) {
-            camera.pitch = Rad::from(Deg(90.0 - 0.0001));
+    #[test]
+    fn view_matrix_matches_look_to_rh() {
+        let poses = [
+            ([0.0, 0.0, -5.0], Deg(90.0), Deg(0.0)),
+            ([1.0, 2.0, 3.0], Deg(0.0), Deg(0.0)),
+            ([-4.0, 0.5, 2.0], Deg(-135.0), Deg(30.0)),
+            ([0.0, 10.0, 0.0], Deg(45.0), Deg(-60.0)),
+            ([2.0, -1.0, 7.0], Deg(200.0), Deg(89.0))
+        ];
+        for (position, yaw, pitch) in poses {
+            let camera = Camera::new(position, yaw, pitch);
+            let expected = Matrix4::look_to_rh(Point3::from(position), yaw_pitch_direction(yaw, pitch), Vector3::unit_y());
+            assert_matrix_eq(camera.view_matrix(), expected);
         }
-        if camera.pitch < Rad::from(Deg(-90.0)) {
-            camera.pitch = Rad::from(Deg(-90.0 + 0.0001));
+    }
+
+    #[test]
+    fn looking_at_matches_look_at_rh() {
+        let eye = Point3::new(3.0, 4.0, -2.0);
+        let target = Point3::new(-1.0, 0.5, 6.0);
+        let camera = Camera::looking_at(eye, target, Vector3::unit_y());
+        assert_matrix_eq(camera.view_matrix(), Matrix4::look_at_rh(eye, target, Vector3::unit_y()));
+        assert_vector_eq(camera.forward(), (target - eye).normalize());
+    }
+
+    #[test]
+    fn roll_turns_the_up_vector() {
+        let camera = Camera::new([0.0, 0.0, 0.0], Deg(90.0), Deg(0.0)).with_roll(Deg(90.0));
+        // Looking along +z, right is -x, so rolling right by 90 degrees points up along -x
+        assert_vector_eq(camera.up(), -Vector3::unit_x());
+        assert_vector_eq(camera.forward(), Vector3::unit_z());
+        let expected = Matrix4::look_to_rh(Point3::new(0.0, 0.0, 0.0), Vector3::unit_z(), -Vector3::unit_x());
+        assert_matrix_eq(camera.view_matrix(), expected);
+    }
+
+    #[test]
+    fn forward_stays_normalized_near_the_poles() {
+        for pitch in [89.0, 89.99, -89.99, 90.0] {
+            let camera = Camera::new([0.0, 0.0, 0.0], Deg(30.0), Deg(pitch));
+            assert!((camera.forward().magnitude() - 1.0).abs() < 1e-5);
         }
     }
+
+    #[test]
+    fn yaw_and_pitch_round_trip() {
+        let camera = Camera::new([0.0, 0.0, 0.0], Deg(-120.0), Deg(25.0));
+        assert!((Deg::from(camera.yaw()).0 - -120.0).abs() < 1e-3);
+        assert!((Deg::from(camera.pitch()).0 - 25.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn rotate_pitch_stops_short_of_straight_up() {
+        let mut camera = Camera::new([0.0, 0.0, 0.0], Deg(0.0), Deg(80.0));
+        camera.rotate_pitch(Deg(45.0));
+        assert!((Deg::from(camera.pitch()).0 - Camera::MAX_PITCH.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn lerp_slerps_between_poses() {
+        let start = Camera::new([0.0, 0.0, 0.0], Deg(0.0), Deg(0.0));
+        let end = Camera::new([2.0, 0.0, 0.0], Deg(90.0), Deg(0.0));
+
+        assert_matrix_eq(start.lerp(&end, 0.0).view_matrix(), start.view_matrix());
+        assert_matrix_eq(start.lerp(&end, 1.0).view_matrix(), end.view_matrix());
+
+        let middle = start.lerp(&end, 0.5);
+        assert_vector_eq(middle.position.to_vec(), Vector3::new(1.0, 0.0, 0.0));
+        assert_vector_eq(middle.forward(), yaw_pitch_direction(Deg(45.0), Deg(0.0)));
+    }
+
+    #[test]
+    fn top_view_looks_straight_down_at_the_focus_point() {
+        let mut camera = Camera::new([0.0, 0.0, -5.0], Deg(90.0), Deg(0.0));
+        camera.set_view(View::Top);
+        assert_vector_eq(camera.forward(), -Vector3::unit_y());
+        assert_vector_eq(camera.up(), Vector3::unit_z());
+        assert_vector_eq(camera.position.to_vec(), Vector3::new(0.0, 5.0, 0.0));
+    }
+
+    fn assert_valid_orientation(camera: &Camera) {
+        let orientation = camera.orientation;
+        assert!([orientation.s, orientation.v.x, orientation.v.y, orientation.v.z].iter().all(|x| x.is_finite()), "{orientation:?}");
+        assert!((orientation.magnitude() - 1.0).abs() < 1e-4);
+        assert!(camera.forward().dot(camera.up()).abs() < 1e-4);
+        let view = camera.view_matrix();
+        let view: &[f32; 16] = view.as_ref();
+        assert!(view.iter().all(|x| x.is_finite()));
+    }
+
+    #[test]
+    fn looking_straight_down_falls_back_to_the_top_view() {
+        let camera = Camera::looking_at([0.0, 5.0, 0.0], [0.0, 0.0, 0.0], Vector3::unit_y());
+        assert_valid_orientation(&camera);
+        assert_vector_eq(camera.forward(), -Vector3::unit_y());
+        assert_vector_eq(camera.up(), Vector3::unit_z());
+    }
+
+    #[test]
+    fn looking_straight_up_stays_valid() {
+        let mut camera = Camera::new([0.0, 0.0, 0.0], Deg(0.0), Deg(0.0));
+        camera.look_at([0.0, 3.0, 0.0], Vector3::unit_y());
+        assert_valid_orientation(&camera);
+        assert_vector_eq(camera.forward(), Vector3::unit_y());
+    }
+
+    #[test]
+    fn looking_along_a_z_up_still_finds_an_up() {
+        let camera = Camera::looking_at([0.0, 0.0, 0.0], [0.0, 0.0, -2.0], Vector3::unit_z());
+        assert_valid_orientation(&camera);
+        assert_vector_eq(camera.forward(), -Vector3::unit_z());
+    }
+
+    fn orthographic(width: f32, height: f32) -> CameraProjection {
+        let mut projection = CameraProjection::new(width, height, 0.1, 100.0);
+        projection.set_mode(ProjectionMode::Orthographic);
+        projection
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel_and_offset_across_the_view() {
+        let projection = orthographic(200.0, 100.0);
+        let camera = Camera::looking_at([2.0, 1.0, 10.0], [2.0, 1.0, 0.0], Vector3::unit_y());
+        let half_height = camera.focus_distance * Rad::tan(camera.fovy / 2.0);
+        let half_width = half_height * 2.0;
+
+        let center = projection.ray(&camera, 0.0, 0.0).unwrap();
+        assert_vector_eq(center.direction, camera.forward());
+        assert_vector_eq(center.origin.to_vec(), camera.position.to_vec() + camera.forward() * 0.1);
+
+        let offset = projection.ray(&camera, 0.5, -0.25).unwrap();
+        assert_vector_eq(offset.direction, camera.forward());
+        assert_vector_eq(offset.origin - center.origin, camera.right() * 0.5 * half_width - camera.up() * 0.25 * half_height);
+    }
+
+    #[test]
+    fn orthographic_rays_hit_what_is_drawn_under_them() {
+        let mut projection = orthographic(200.0, 100.0);
+        let camera = Camera::looking_at([0.0, 0.0, 10.0], [0.0, 0.0, 0.0], Vector3::unit_y());
+        // Far off to the side and deep, where a perspective ray through the same pixel misses it
+        let aabb = Aabb::new([3.0, -1.5, -40.0], [3.5, -1.0, -39.0]);
+        let ndc = Point3::from_homogeneous(projection.view_proj_matrix(&camera) * aabb.center().to_homogeneous());
+        // The pixel of a 200 by 100 target the box's center is drawn at
+        let (x, y) = ((ndc.x + 1.0) * 100.0, (1.0 - ndc.y) * 50.0);
+
+        let ray = projection.pixel_ray(&camera, x, y, 200, 100).unwrap();
+        assert!((ray.intersect_aabb(&aabb).unwrap() - (10.0 + 39.0 - 0.1)).abs() < 1e-3);
+        assert_eq!(projection.pixel_ray(&camera, x + 10.0, y, 200, 100).unwrap().intersect_aabb(&aabb), None);
+
+        projection.set_mode(ProjectionMode::Perspective);
+        assert_eq!(projection.pixel_ray(&camera, x, y, 200, 100).unwrap().intersect_aabb(&aabb), None);
+    }
+
 }
@@ -0,0 +1,248 @@
+use crate::camera::Camera;
+use crate::error::{
+    Error,
+    Result
+};
+
+use cgmath::{
+    Deg,
+    InnerSpace,
+    Point3,
+    Quaternion,
+    Vector3
+};
+use serde::{
+    Deserialize,
+    Serialize
+};
+use std::{
+    fs,
+    path::Path
+};
+
+// A camera pose at a point in time. The orientation is a quaternion as (x, y, z, w), since paths
+// are recorded rather than written by hand; the field of view is in degrees
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Keyframe {
+    // Seconds from the start of the path
+    pub time: f32,
+    pub position: [f32; 3],
+    pub orientation: [f32; 4],
+    pub fov: f32,
+    pub focus_distance: f32
+}
+
+impl Keyframe {
+    pub fn new(time: f32, camera: &Camera) -> Self {
+        let Quaternion { s, v } = camera.orientation;
+        Self {
+            time,
+            position: camera.position.into(),
+            orientation: [v.x, v.y, v.z, s],
+            fov: Deg::from(camera.fovy).0,
+            focus_distance: camera.focus_distance
+        }
+    }
+
+    pub fn camera(&self) -> Camera {
+        let [x, y, z, w] = self.orientation;
+        let mut camera = Camera::from_orientation(self.position, Quaternion::new(w, x, y, z));
+        camera.fovy = Deg(self.fov).into();
+        camera.focus_distance = self.focus_distance;
+        camera
+    }
+}
+
+// Keyframes in time order, played back along a Catmull-Rom spline through the positions while
+// the orientation slerps from one keyframe to the next
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CameraPath {
+    keyframes: Vec<Keyframe>
+}
+
+impl CameraPath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|source| Error::Io { path: path.to_path_buf(), source })?;
+        let mut camera_path: Self = ron::from_str(&text).map_err(|source| Error::CameraPathParse { path: path.to_path_buf(), source })?;
+        // A zero quaternion has no rotation to normalize to and would turn the camera into NaNs
+        let invalid = camera_path.keyframes.iter().position(|keyframe| {
+            let [x, y, z, w] = keyframe.orientation;
+            let length = Quaternion::new(w, x, y, z).magnitude2();
+            !(length.is_finite() && length > f32::EPSILON)
+        });
+        if let Some(index) = invalid {
+            return Err(Error::InvalidKeyframe { path: path.to_path_buf(), index });
+        }
+        // Hand-edited files may be out of order
+        camera_path.keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Ok(camera_path)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(Error::CameraPathSerialize)?;
+        fs::write(path, text).map_err(|source| Error::Io { path: path.to_path_buf(), source })
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keyframes.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.keyframes.clear();
+    }
+
+    // Keeps the keyframes in time order; one at the same time as an existing one goes after it
+    pub fn add_keyframe(&mut self, keyframe: Keyframe) {
+        let index = self.keyframes.partition_point(|existing| existing.time <= keyframe.time);
+        self.keyframes.insert(index, keyframe);
+    }
+
+    // Time of the last keyframe, in seconds
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    // The pose at time seconds, holding the first and last keyframes outside the path. None if
+    // there are no keyframes
+    pub fn sample(&self, time: f32) -> Option<Camera> {
+        let last = self.keyframes.len().checked_sub(1)?;
+        let next = self.keyframes.partition_point(|keyframe| keyframe.time <= time);
+        if next == 0 {
+            return Some(self.keyframes[0].camera());
+        }
+        if next > last {
+            return Some(self.keyframes[last].camera());
+        }
+
+        let index = next - 1;
+        // Landing on a keyframe plays it back as recorded rather than as the slerp rounds it
+        if time == self.keyframes[index].time {
+            return Some(self.keyframes[index].camera());
+        }
+        let (from, to) = (self.keyframes[index].camera(), self.keyframes[next].camera());
+        let span = self.keyframes[next].time - self.keyframes[index].time;
+        let amount = if span > 0.0 { (time - self.keyframes[index].time) / span } else { 1.0 };
+
+        // The spline needs a keyframe either side of the segment; the ends repeat themselves
+        let before = Point3::from(self.keyframes[index.saturating_sub(1)].position);
+        let after = Point3::from(self.keyframes[(next + 1).min(last)].position);
+
+        // Everything but the position interpolates as it does between simulation steps
+        let mut camera = from.lerp(&to, amount);
+        camera.position = catmull_rom(before, from.position, to.position, after, amount);
+        Some(camera)
+    }
+}
+
+// Uniform Catmull-Rom, passing through p1 at amount 0 and p2 at amount 1
+fn catmull_rom(p0: Point3<f32>, p1: Point3<f32>, p2: Point3<f32>, p3: Point3<f32>, amount: f32) -> Point3<f32> {
+    let (t, t2, t3) = (amount, amount * amount, amount * amount * amount);
+    let [p0, p1, p2, p3] = [p0, p1, p2, p3].map(|point| Vector3::new(point.x, point.y, point.z));
+    let point = (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3) * 0.5;
+    Point3::new(point.x, point.y, point.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        env,
+        process
+    };
+
+    fn keyframe(time: f32, x: f32, yaw: f32) -> Keyframe {
+        Keyframe::new(time, &Camera::new([x, 1.0, 0.0], Deg(yaw), Deg(0.0)))
+    }
+
+    fn path() -> CameraPath {
+        let mut path = CameraPath::new();
+        for (time, x, yaw) in [(0.0, 0.0, 0.0), (1.0, 2.0, 30.0), (3.0, 3.0, 90.0), (4.0, 7.0, 45.0)] {
+            path.add_keyframe(keyframe(time, x, yaw));
+        }
+        path
+    }
+
+    // Compared as keyframes since cameras have no PartialEq
+    fn assert_same_pose(camera: Option<Camera>, keyframe: &Keyframe) {
+        assert_eq!(camera.map(|camera| Keyframe::new(keyframe.time, &camera)), Some(Keyframe::new(keyframe.time, &keyframe.camera())));
+    }
+
+    #[test]
+    fn sampling_at_a_keyframe_returns_it_exactly() {
+        let path = path();
+        for keyframe in path.keyframes() {
+            assert_same_pose(path.sample(keyframe.time), keyframe);
+        }
+    }
+
+    #[test]
+    fn the_ends_are_held_outside_the_path() {
+        let path = path();
+        let keyframes = path.keyframes();
+        assert_same_pose(path.sample(-5.0), &keyframes[0]);
+        assert_same_pose(path.sample(100.0), &keyframes[3]);
+        assert_eq!(path.duration(), 4.0);
+        assert!(CameraPath::new().sample(0.0).is_none());
+    }
+
+    #[test]
+    fn samples_between_keyframes_stay_on_the_segment() {
+        let camera = path().sample(2.0).unwrap();
+        assert!(camera.position.x > 2.0 && camera.position.x < 3.0, "{:?}", camera.position);
+        assert!((camera.orientation.magnitude() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn catmull_rom_passes_through_the_middle_points() {
+        let points = [[0.0, 0.0, 0.0], [1.0, 2.0, 0.0], [3.0, 2.0, 1.0], [4.0, 0.0, 0.0]].map(Point3::from);
+        assert_eq!(catmull_rom(points[0], points[1], points[2], points[3], 0.0), points[1]);
+        assert_eq!(catmull_rom(points[0], points[1], points[2], points[3], 1.0), points[2]);
+    }
+
+    #[test]
+    fn keyframes_are_kept_in_time_order() {
+        let mut path = CameraPath::new();
+        for (time, x) in [(2.0, 0.0), (0.0, 1.0), (1.0, 2.0), (1.0, 3.0)] {
+            path.add_keyframe(keyframe(time, x, 0.0));
+        }
+        let order: Vec<_> = path.keyframes().iter().map(|keyframe| (keyframe.time, keyframe.position[0])).collect();
+        // A keyframe at the same time as another goes after it
+        assert_eq!(order, [(0.0, 1.0), (1.0, 2.0), (1.0, 3.0), (2.0, 0.0)]);
+    }
+
+    #[test]
+    fn loading_sorts_the_keyframes() {
+        let file = env::temp_dir().join(format!("camera_path_sorted_{}.ron", process::id()));
+        let mut keyframes = path().keyframes;
+        keyframes.reverse();
+        CameraPath { keyframes }.save(&file).unwrap();
+        let loaded = CameraPath::load(&file);
+        let _ = fs::remove_file(&file);
+        assert_eq!(loaded.unwrap(), path());
+    }
+
+    #[test]
+    fn zero_orientations_are_rejected_on_load() {
+        let file = env::temp_dir().join(format!("camera_path_zero_orientation_{}.ron", process::id()));
+        let mut path = path();
+        path.keyframes[2].orientation = [0.0; 4];
+        path.save(&file).unwrap();
+        let loaded = CameraPath::load(&file);
+        let _ = fs::remove_file(&file);
+        assert!(matches!(loaded, Err(Error::InvalidKeyframe { index: 2, .. })));
+    }
+}
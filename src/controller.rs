@@ -0,0 +1,439 @@
+use crate::camera::Camera;
+use crate::bounds::Aabb;
+use crate::input::{
+    self,
+    Input
+};
+
+use cgmath::{
+    Deg,
+    InnerSpace,
+    Point3,
+    Rad,
+    Vector3
+};
+use std::time::Duration;
+
+// Moves the camera in response to input, once per frame with the real frame time, so it keeps
+// working while the simulation is paused or slowed down. The app can hold several and switch between
+// them at runtime
+pub trait CameraController {
+    // Called when the controller takes over, so it can carry on from wherever the camera is
+    fn activate(&mut self, _camera: &Camera) {}
+
+    fn update_camera(&mut self, camera: &mut Camera, input: &mut Input, deltatime: &Duration);
+
+    // Moves the camera back along its view direction until the bounds fit the view
+    fn frame(&mut self, camera: &mut Camera, bounds: &Aabb, aspect: f32) {
+        camera.focus_distance = camera.framing_distance(bounds.radius(), aspect);
+        camera.position = bounds.center() - camera.forward() * camera.focus_distance;
+    }
+}
+
+// Each wheel notch scales the move speed, field of view or orbit distance by this
+const WHEEL_STEP: f32 = 1.1;
+
+// What scrolling the mouse wheel changes
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum WheelMode {
+    #[default]
+    MoveSpeed,
+    Zoom
+}
+
+// First-person flying: move along the view direction, look around with the look axes or mouse-look.
+// Movement has some inertia, speeding up towards the input and slowing down once it stops
+#[derive(Clone, Debug)]
+pub struct FlyController {
+    // Top speed in units per second, before sprinting
+    move_speed: f32,
+    sprint_multiplier: f32,
+    // How quickly the velocity catches up with the input, and how quickly it dies down without any,
+    // as rates per second; higher feels snappier
+    acceleration: f32,
+    damping: f32,
+    velocity: Vector3<f32>,
+    rot_speed: f32,
+    // Radians per pixel of mouse movement
+    mouse_sensitivity: f32,
+    invert_mouse_y: bool,
+    wheel_mode: WheelMode
+}
+
+impl FlyController {
+    const MIN_FOVY: Deg<f32> = Deg(5.0);
+    const MAX_FOVY: Deg<f32> = Deg(120.0);
+    const MIN_MOVE_SPEED: f32 = 0.01;
+    const MAX_MOVE_SPEED: f32 = 1000.0;
+
+    pub fn new(move_speed: f32, rot_speed: f32) -> Self {
+        Self {
+            move_speed: move_speed.clamp(Self::MIN_MOVE_SPEED, Self::MAX_MOVE_SPEED),
+            sprint_multiplier: 3.0,
+            acceleration: 10.0,
+            damping: 8.0,
+            velocity: Vector3::new(0.0, 0.0, 0.0),
+            rot_speed,
+            mouse_sensitivity: 0.003,
+            invert_mouse_y: false,
+            wheel_mode: WheelMode::default()
+        }
+    }
+
+    pub fn move_speed(&self) -> f32 {
+        self.move_speed
+    }
+
+    // Also changed by the adjust_move_speed axis and, in WheelMode::MoveSpeed, the mouse wheel
+    pub fn set_move_speed(&mut self, move_speed: f32) {
+        self.move_speed = move_speed.clamp(Self::MIN_MOVE_SPEED, Self::MAX_MOVE_SPEED);
+    }
+
+    pub fn sprint_multiplier(&self) -> f32 {
+        self.sprint_multiplier
+    }
+
+    pub fn set_sprint_multiplier(&mut self, sprint_multiplier: f32) {
+        self.sprint_multiplier = sprint_multiplier;
+    }
+
+    pub fn acceleration(&self) -> f32 {
+        self.acceleration
+    }
+
+    // Infinity makes the camera reach full speed instantly
+    pub fn set_acceleration(&mut self, acceleration: f32) {
+        self.acceleration = acceleration;
+    }
+
+    pub fn damping(&self) -> f32 {
+        self.damping
+    }
+
+    // Infinity makes the camera stop instantly
+    pub fn set_damping(&mut self, damping: f32) {
+        self.damping = damping;
+    }
+
+    pub fn velocity(&self) -> Vector3<f32> {
+        self.velocity
+    }
+
+    pub fn mouse_sensitivity(&self) -> f32 {
+        self.mouse_sensitivity
+    }
+
+    pub fn set_mouse_sensitivity(&mut self, mouse_sensitivity: f32) {
+        self.mouse_sensitivity = mouse_sensitivity;
+    }
+
+    pub fn invert_mouse_y(&self) -> bool {
+        self.invert_mouse_y
+    }
+
+    pub fn set_invert_mouse_y(&mut self, invert_mouse_y: bool) {
+        self.invert_mouse_y = invert_mouse_y;
+    }
+
+    pub fn wheel_mode(&self) -> WheelMode {
+        self.wheel_mode
+    }
+
+    pub fn set_wheel_mode(&mut self, wheel_mode: WheelMode) {
+        self.wheel_mode = wheel_mode;
+    }
+}
+
+impl Default for FlyController {
+    fn default() -> Self {
+        Self::new(4.0, 2.0)
+    }
+}
+
+impl CameraController for FlyController {
+    // Starts at rest rather than with whatever velocity it had when last active
+    fn activate(&mut self, _camera: &Camera) {
+        self.velocity = Vector3::new(0.0, 0.0, 0.0);
+    }
+
+    // Reads the movement and look axes, so which keys drive it is up to the input bindings. Mouse
+    // motion and wheel scrolling are taken from the input, so a frame's worth only applies once
+    fn update_camera(&mut self, camera: &mut Camera, input: &mut Input, deltatime: &Duration) {
+        let deltatime = deltatime.as_secs_f32();
+
+        // Held, the speed doubles or halves every second
+        self.set_move_speed(self.move_speed * 2.0f32.powf(input.axis(input::ADJUST_MOVE_SPEED) * deltatime));
+
+        let wheel = input.take_wheel();
+        if wheel != 0.0 {
+            let scale = WHEEL_STEP.powf(wheel);
+            match self.wheel_mode {
+                WheelMode::MoveSpeed => self.set_move_speed(self.move_speed * scale),
+                WheelMode::Zoom => {
+                    let fovy = (camera.fovy.0 / scale).clamp(Rad::from(Self::MIN_FOVY).0, Rad::from(Self::MAX_FOVY).0);
+                    camera.fovy = Rad(fovy);
+                }
+            }
+        }
+
+        let (forward, right) = camera.dirs_forward_right();
+
+        // Capped at length 1, so pressing two directions at once is no faster than one, while
+        // analog sticks still allow anything slower
+        let mut wish = input.axis(input::MOVE_FORWARD) * forward
+            + input.axis(input::MOVE_RIGHT) * right
+            + input.axis(input::MOVE_UP) * Vector3::unit_y();
+        if wish.magnitude2() > 1.0 {
+            wish = wish.normalize();
+        }
+        let sprint = if input.action_held(input::SPRINT) { self.sprint_multiplier } else { 1.0 };
+        let target_velocity = wish * self.move_speed * sprint;
+
+        // Exponential approach, which behaves the same whatever the step length
+        let rate = if wish.magnitude2() > 0.0 { self.acceleration } else { self.damping };
+        self.velocity += (target_velocity - self.velocity) * (1.0 - (-rate * deltatime).exp());
+        camera.position += self.velocity * deltatime;
+
+        // Motion is taken either way, so turning mouse-look on does not replay earlier movement.
+        // Moving the mouse up (a negative y) looks up unless inverted
+        let (mouse_x, mouse_y) = input.take_mouse_motion();
+        let (mouse_x, mouse_y) = match (input.mouse_look(), self.invert_mouse_y) {
+            (false, _) => (0.0, 0.0),
+            (true, false) => (mouse_x, -mouse_y),
+            (true, true) => (mouse_x, mouse_y)
+        };
+
+        camera.rotate_yaw(Rad(input.axis(input::LOOK_RIGHT) * self.rot_speed * deltatime + mouse_x * self.mouse_sensitivity));
+        camera.rotate_pitch(Rad(input.axis(input::LOOK_UP) * self.rot_speed * deltatime + mouse_y * self.mouse_sensitivity));
+        camera.rotate_roll(Rad(input.axis(input::ROLL_RIGHT) * self.rot_speed * deltatime));
+    }
+}
+
+// Circles a target point for inspecting models: the orbit action (left drag by default) rotates,
+// the pan action (middle drag) moves the target and the wheel zooms. The look axes rotate too, move
+// forward zooms and the other move axes pan
+#[derive(Clone, Debug)]
+pub struct OrbitController {
+    target: Point3<f32>,
+    distance: f32,
+    rot_speed: f32,
+    // Radians per pixel of mouse movement
+    mouse_sensitivity: f32,
+    // Fraction of the distance to the target moved per pixel of mouse movement
+    pan_sensitivity: f32
+}
+
+impl OrbitController {
+    const MIN_DISTANCE: f32 = 0.01;
+
+    pub fn new<P: Into<Point3<f32>>>(target: P, distance: f32) -> Self {
+        Self {
+            target: target.into(),
+            distance: distance.max(Self::MIN_DISTANCE),
+            rot_speed: 2.0,
+            mouse_sensitivity: 0.01,
+            pan_sensitivity: 0.002
+        }
+    }
+
+    pub fn target(&self) -> Point3<f32> {
+        self.target
+    }
+
+    pub fn set_target<P: Into<Point3<f32>>>(&mut self, target: P) {
+        self.target = target.into();
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.distance
+    }
+
+    pub fn set_distance(&mut self, distance: f32) {
+        self.distance = distance.max(Self::MIN_DISTANCE);
+    }
+
+    pub fn mouse_sensitivity(&self) -> f32 {
+        self.mouse_sensitivity
+    }
+
+    pub fn set_mouse_sensitivity(&mut self, mouse_sensitivity: f32) {
+        self.mouse_sensitivity = mouse_sensitivity;
+    }
+
+    pub fn pan_sensitivity(&self) -> f32 {
+        self.pan_sensitivity
+    }
+
+    pub fn set_pan_sensitivity(&mut self, pan_sensitivity: f32) {
+        self.pan_sensitivity = pan_sensitivity;
+    }
+
+    fn place_camera(&self, camera: &mut Camera) {
+        camera.focus_distance = self.distance;
+        camera.position = self.target - camera.forward() * self.distance;
+    }
+}
+
+impl Default for OrbitController {
+    // Orbits the origin from the default camera's distance
+    fn default() -> Self {
+        Self::new([0.0, 0.0, 0.0], 5.0)
+    }
+}
+
+impl CameraController for OrbitController {
+    // Keeps the camera where it is and orbits whatever it is focused on
+    fn activate(&mut self, camera: &Camera) {
+        self.set_distance(camera.focus_distance);
+        self.target = camera.focus_point();
+    }
+
+    fn update_camera(&mut self, camera: &mut Camera, input: &mut Input, deltatime: &Duration) {
+        let deltatime = deltatime.as_secs_f32();
+
+        // Held, move forward halves the distance every second; as a power it stays positive however
+        // long the frame
+        let zoom = WHEEL_STEP.powf(input.take_wheel()) * 2.0f32.powf(input.axis(input::MOVE_FORWARD) * deltatime);
+        self.set_distance(self.distance / zoom);
+
+        // Dragging right or down turns the model the same way, which moves the camera the other
+        let (mouse_x, mouse_y) = input.take_mouse_motion();
+        let (orbit_x, orbit_y) = if input.action_held(input::ORBIT) {
+            (mouse_x * self.mouse_sensitivity, mouse_y * self.mouse_sensitivity)
+        } else {
+            (0.0, 0.0)
+        };
+        camera.rotate_yaw(Rad(input.axis(input::LOOK_RIGHT) * self.rot_speed * deltatime + orbit_x));
+        camera.rotate_pitch(Rad(input.axis(input::LOOK_UP) * self.rot_speed * deltatime - orbit_y));
+
+        // Panning drags the target with the cursor, at a speed that matches the zoom level
+        let (right, up) = (camera.right(), camera.up());
+        let (pan_x, pan_y) = if input.action_held(input::PAN) {
+            (-mouse_x * self.pan_sensitivity, mouse_y * self.pan_sensitivity)
+        } else {
+            (0.0, 0.0)
+        };
+        let pan_x = pan_x + input.axis(input::MOVE_RIGHT) * deltatime;
+        let pan_y = pan_y + input.axis(input::MOVE_UP) * deltatime;
+        self.target += (right * pan_x + up * pan_y) * self.distance;
+
+        self.place_camera(camera);
+    }
+
+    fn frame(&mut self, camera: &mut Camera, bounds: &Aabb, aspect: f32) {
+        self.target = bounds.center();
+        self.set_distance(camera.framing_distance(bounds.radius(), aspect));
+        self.place_camera(camera);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::InputBindings;
+
+    use sdl2::{
+        event::Event,
+        keyboard::{
+            Keycode,
+            Mod,
+            Scancode
+        },
+        mouse::MouseWheelDirection
+    };
+
+    fn key(down: bool, scancode: Scancode) -> Event {
+        let (timestamp, window_id, keycode, scancode, keymod, repeat) = (0, 0, Keycode::from_scancode(scancode), Some(scancode), Mod::NOMOD, false);
+        if down {
+            Event::KeyDown { timestamp, window_id, keycode, scancode, keymod, repeat }
+        } else {
+            Event::KeyUp { timestamp, window_id, keycode, scancode, keymod, repeat }
+        }
+    }
+
+    fn wheel(notches: f32) -> Event {
+        Event::MouseWheel {
+            timestamp: 0,
+            window_id: 0,
+            which: 0,
+            x: 0,
+            y: notches as i32,
+            direction: MouseWheelDirection::Normal,
+            precise_x: 0.0,
+            precise_y: notches
+        }
+    }
+
+    // Runs the controller for some seconds in 10 ms frames
+    fn run_for(seconds: u32, controller: &mut impl CameraController, camera: &mut Camera, input: &mut Input) {
+        for _ in 0..seconds * 100 {
+            controller.update_camera(camera, input, &Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn fly_velocity_approaches_the_input_and_damps_to_zero() {
+        let mut controller = FlyController::default();
+        let mut camera = Camera::new([0.0, 0.0, 0.0], Deg(0.0), Deg(0.0));
+        let mut input = Input::new(InputBindings::default());
+
+        input.process_event(&key(true, Scancode::W));
+        controller.update_camera(&mut camera, &mut input, &Duration::from_millis(10));
+        let first = controller.velocity().magnitude();
+        assert!(first > 0.0 && first < controller.move_speed() / 2.0, "{first}");
+
+        run_for(1, &mut controller, &mut camera, &mut input);
+        assert!((controller.velocity() - camera.forward() * controller.move_speed()).magnitude() < 1e-3, "{:?}", controller.velocity());
+
+        input.process_event(&key(false, Scancode::W));
+        run_for(2, &mut controller, &mut camera, &mut input);
+        assert!(controller.velocity().magnitude() < 1e-3, "{:?}", controller.velocity());
+    }
+
+    #[test]
+    fn sprinting_multiplies_the_top_speed() {
+        let mut controller = FlyController::default();
+        let mut camera = Camera::new([0.0, 0.0, 0.0], Deg(0.0), Deg(0.0));
+        let mut input = Input::new(InputBindings::default());
+        input.process_event(&key(true, Scancode::W));
+        input.process_event(&key(true, Scancode::LCtrl));
+
+        run_for(1, &mut controller, &mut camera, &mut input);
+        let top_speed = controller.move_speed() * controller.sprint_multiplier();
+        assert!((controller.velocity().magnitude() - top_speed).abs() < 1e-3, "{:?}", controller.velocity());
+    }
+
+    #[test]
+    fn wheel_zoom_keeps_the_field_of_view_in_range() {
+        let mut controller = FlyController::default();
+        controller.set_wheel_mode(WheelMode::Zoom);
+        let mut camera = Camera::new([0.0, 0.0, 0.0], Deg(0.0), Deg(0.0));
+        let mut input = Input::new(InputBindings::default());
+
+        input.process_event(&wheel(100.0));
+        controller.update_camera(&mut camera, &mut input, &Duration::from_millis(10));
+        assert!((Deg::from(camera.fovy).0 - FlyController::MIN_FOVY.0).abs() < 1e-4);
+
+        input.process_event(&wheel(-100.0));
+        controller.update_camera(&mut camera, &mut input, &Duration::from_millis(10));
+        assert!((Deg::from(camera.fovy).0 - FlyController::MAX_FOVY.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn orbit_distance_stays_positive() {
+        let mut controller = OrbitController::default();
+        let mut camera = Camera::new([0.0, 0.0, -5.0], Deg(90.0), Deg(0.0));
+        let mut input = Input::new(InputBindings::default());
+
+        input.process_event(&wheel(1000.0));
+        controller.update_camera(&mut camera, &mut input, &Duration::from_millis(10));
+        assert_eq!(controller.distance(), OrbitController::MIN_DISTANCE);
+
+        // Zooming out through a two second frame doubles the distance twice
+        controller.set_distance(5.0);
+        input.process_event(&key(true, Scancode::S));
+        controller.update_camera(&mut camera, &mut input, &Duration::from_secs(2));
+        assert!((controller.distance() - 20.0).abs() < 1e-4, "{}", controller.distance());
+        assert!(((camera.position - controller.target()).magnitude() - 20.0).abs() < 1e-3);
+    }
+}
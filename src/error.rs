@@ -0,0 +1,81 @@
+use crate::scene::NodeId;
+use crate::vfs::AssetRoot;
+
+use std::{
+    io,
+    path::PathBuf
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("asset {path} not found in any of {roots:?}")]
+    AssetNotFound { path: PathBuf, roots: Vec<AssetRoot> },
+
+    #[error("failed to read asset {path}")]
+    Io { path: PathBuf, #[source] source: io::Error },
+
+    #[error("failed to decode image {path}")]
+    Decode { path: PathBuf, #[source] source: image::ImageError },
+
+    #[error("failed to parse {path} at line {line}")]
+    Parse { path: PathBuf, line: usize, #[source] source: tobj::LoadError },
+
+    #[error("no compatible graphics adapter found (force_fallback_adapter: {force_fallback_adapter})")]
+    AdapterUnavailable { force_fallback_adapter: bool },
+
+    #[error("failed to create graphics device")]
+    DeviceUnavailable(#[from] wgpu::RequestDeviceError),
+
+    #[error("failed to create window surface")]
+    CreateSurface(#[from] wgpu::CreateSurfaceError),
+
+    #[error("failed to acquire the next surface texture")]
+    Surface(#[from] wgpu::SurfaceError),
+
+    #[error("SDL error: {0}")]
+    Sdl(String),
+
+    #[error("failed to read back the rendered frame")]
+    Readback(#[from] wgpu::BufferAsyncError),
+
+    #[error("frame readback is only supported for offscreen targets")]
+    ReadbackUnsupported,
+
+    #[error("headless apps have no event loop, use App::run_headless")]
+    NoEventLoop,
+
+    #[error("failed to write image {path}")]
+    Encode { path: PathBuf, #[source] source: image::ImageError },
+
+    #[error("failed to parse scene file {path}")]
+    SceneParse { path: PathBuf, #[source] source: ron::error::SpannedError },
+
+    #[error("failed to serialize scene file")]
+    SceneSerialize(#[from] ron::Error),
+
+    #[error("failed to parse input bindings {path}")]
+    BindingsParse { path: PathBuf, #[source] source: ron::error::SpannedError },
+
+    #[error("failed to serialize input bindings")]
+    BindingsSerialize(#[source] ron::Error),
+
+    #[error("failed to parse camera path {path}")]
+    CameraPathParse { path: PathBuf, #[source] source: ron::error::SpannedError },
+
+    #[error("failed to serialize camera path")]
+    CameraPathSerialize(#[source] ron::Error),
+
+    #[error("keyframe {index} of camera path {path} has a zero length orientation")]
+    InvalidKeyframe { path: PathBuf, index: usize },
+
+    #[error("the camera path has no keyframes")]
+    EmptyCameraPath,
+
+    #[error("cannot parent {node:?} to its own descendant {parent:?}")]
+    InvalidParent { node: NodeId, parent: NodeId },
+
+    #[error("{0:?} does not refer to a live node")]
+    DespawnedNode(NodeId)
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,551 @@
+use crate::error::{
+    Error,
+    Result
+};
+
+use sdl2::{
+    controller::{
+        Axis,
+        Button
+    },
+    event::{
+        Event,
+        WindowEvent
+    },
+    keyboard::{
+        Keycode,
+        Scancode
+    },
+    mouse::{
+        MouseButton,
+        MouseWheelDirection
+    }
+};
+use serde::{
+    Deserialize,
+    Serialize
+};
+use std::{
+    collections::{
+        BTreeMap,
+        HashMap,
+        HashSet
+    },
+    fs,
+    path::Path
+};
+
+// Names of the actions and axes the built-in controls read
+pub const MOVE_FORWARD: &str = "move_forward";
+pub const MOVE_RIGHT: &str = "move_right";
+pub const MOVE_UP: &str = "move_up";
+pub const LOOK_RIGHT: &str = "look_right";
+pub const LOOK_UP: &str = "look_up";
+pub const ROLL_RIGHT: &str = "roll_right";
+pub const ADJUST_MOVE_SPEED: &str = "adjust_move_speed";
+pub const SPRINT: &str = "sprint";
+pub const MOUSE_LOOK: &str = "mouse_look";
+pub const TOGGLE_MOUSE_LOOK: &str = "toggle_mouse_look";
+pub const ORBIT: &str = "orbit";
+pub const PAN: &str = "pan";
+pub const SWITCH_CAMERA_CONTROLLER: &str = "switch_camera_controller";
+pub const FRAME_SCENE: &str = "frame_scene";
+pub const TOGGLE_PROJECTION: &str = "toggle_projection";
+pub const VIEW_FRONT: &str = "view_front";
+pub const VIEW_SIDE: &str = "view_side";
+pub const VIEW_TOP: &str = "view_top";
+pub const RECORD_KEYFRAME: &str = "record_keyframe";
+pub const PLAY_CAMERA_PATH: &str = "play_camera_path";
+pub const QUIT: &str = "quit";
+pub const PAUSE: &str = "pause";
+pub const SINGLE_STEP: &str = "single_step";
+pub const SLOWER: &str = "slower";
+pub const FASTER: &str = "faster";
+
+// A physical input. Scancodes name a key by its position on a US layout, so they stay in place on
+// AZERTY or Dvorak; keycodes name the character the key produces in the current layout
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Key(#[serde(with = "keycode_name")] Keycode),
+    Scancode(#[serde(with = "scancode_name")] Scancode),
+    Mouse(#[serde(with = "mouse_button_name")] MouseButton),
+    // Held on any connected gamepad
+    ControllerButton(#[serde(with = "controller_button_name")] Button)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AxisBinding {
+    // -1 while only negative is held, 1 while only positive is, and 0 for both or neither
+    Buttons { negative: Binding, positive: Binding },
+    // A gamepad stick or trigger, past the deadzone, multiplied by scale (-1 flips it). Stick y
+    // axes are positive downwards and triggers go from 0 to 1
+    ControllerAxis {
+        #[serde(with = "controller_axis_name")]
+        axis: Axis,
+        #[serde(default = "default_axis_scale")]
+        scale: f32
+    }
+}
+
+fn default_axis_scale() -> f32 {
+    1.0
+}
+
+// Maps action and axis names to the inputs that drive them, stored as RON
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputBindings {
+    pub actions: BTreeMap<String, Vec<Binding>>,
+    pub axes: BTreeMap<String, Vec<AxisBinding>>,
+    // Gamepad axis values closer to rest than this, from 0 to 1, read as 0
+    pub controller_deadzone: f32
+}
+
+impl InputBindings {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|source| Error::Io { path: path.to_path_buf(), source })?;
+        ron::from_str(&text).map_err(|source| Error::BindingsParse { path: path.to_path_buf(), source })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(Error::BindingsSerialize)?;
+        fs::write(path, text).map_err(|source| Error::Io { path: path.to_path_buf(), source })
+    }
+
+    pub fn bind_action<S: Into<String>>(&mut self, action: S, binding: Binding) {
+        self.actions.entry(action.into()).or_default().push(binding);
+    }
+
+    pub fn bind_axis<S: Into<String>>(&mut self, axis: S, binding: AxisBinding) {
+        self.axes.entry(axis.into()).or_default().push(binding);
+    }
+}
+
+impl Default for InputBindings {
+    // WASD movement by key position, arrow keys to look around, and the debug time controls. On a
+    // gamepad the left stick moves, the right stick looks and the triggers move down and up
+    fn default() -> Self {
+        let buttons = |negative, positive| AxisBinding::Buttons { negative, positive };
+        let controller_axis = |axis, scale| AxisBinding::ControllerAxis { axis, scale };
+        let mut bindings = Self {
+            actions: BTreeMap::new(),
+            axes: BTreeMap::new(),
+            controller_deadzone: 0.15
+        };
+
+        bindings.bind_axis(MOVE_FORWARD, buttons(Binding::Scancode(Scancode::S), Binding::Scancode(Scancode::W)));
+        bindings.bind_axis(MOVE_RIGHT, buttons(Binding::Scancode(Scancode::A), Binding::Scancode(Scancode::D)));
+        bindings.bind_axis(MOVE_UP, buttons(Binding::Scancode(Scancode::LShift), Binding::Scancode(Scancode::Space)));
+        bindings.bind_axis(LOOK_RIGHT, buttons(Binding::Scancode(Scancode::Left), Binding::Scancode(Scancode::Right)));
+        bindings.bind_axis(LOOK_UP, buttons(Binding::Scancode(Scancode::Down), Binding::Scancode(Scancode::Up)));
+        bindings.bind_axis(ROLL_RIGHT, buttons(Binding::Scancode(Scancode::Q), Binding::Scancode(Scancode::E)));
+        bindings.bind_axis(ADJUST_MOVE_SPEED, buttons(Binding::Key(Keycode::Minus), Binding::Key(Keycode::Equals)));
+        bindings.bind_axis(ADJUST_MOVE_SPEED, buttons(Binding::Key(Keycode::KpMinus), Binding::Key(Keycode::KpPlus)));
+
+        bindings.bind_axis(MOVE_FORWARD, controller_axis(Axis::LeftY, -1.0));
+        bindings.bind_axis(MOVE_RIGHT, controller_axis(Axis::LeftX, 1.0));
+        bindings.bind_axis(MOVE_UP, controller_axis(Axis::TriggerRight, 1.0));
+        bindings.bind_axis(MOVE_UP, controller_axis(Axis::TriggerLeft, -1.0));
+        bindings.bind_axis(LOOK_RIGHT, controller_axis(Axis::RightX, 1.0));
+        bindings.bind_axis(LOOK_UP, controller_axis(Axis::RightY, -1.0));
+        bindings.bind_axis(ROLL_RIGHT, buttons(Binding::ControllerButton(Button::LeftShoulder), Binding::ControllerButton(Button::RightShoulder)));
+        bindings.bind_axis(ADJUST_MOVE_SPEED, buttons(Binding::ControllerButton(Button::DPadDown), Binding::ControllerButton(Button::DPadUp)));
+
+        bindings.bind_action(MOUSE_LOOK, Binding::Mouse(MouseButton::Right));
+        bindings.bind_action(TOGGLE_MOUSE_LOOK, Binding::Key(Keycode::Tab));
+        bindings.bind_action(SPRINT, Binding::Scancode(Scancode::LCtrl));
+        bindings.bind_action(SPRINT, Binding::ControllerButton(Button::LeftStick));
+        bindings.bind_action(ORBIT, Binding::Mouse(MouseButton::Left));
+        bindings.bind_action(PAN, Binding::Mouse(MouseButton::Middle));
+        bindings.bind_action(SWITCH_CAMERA_CONTROLLER, Binding::Key(Keycode::C));
+        bindings.bind_action(SWITCH_CAMERA_CONTROLLER, Binding::ControllerButton(Button::Back));
+        bindings.bind_action(FRAME_SCENE, Binding::Key(Keycode::F));
+        bindings.bind_action(FRAME_SCENE, Binding::ControllerButton(Button::Y));
+        bindings.bind_action(TOGGLE_PROJECTION, Binding::Key(Keycode::O));
+        bindings.bind_action(TOGGLE_PROJECTION, Binding::Key(Keycode::Kp5));
+        bindings.bind_action(VIEW_FRONT, Binding::Key(Keycode::Kp1));
+        bindings.bind_action(VIEW_SIDE, Binding::Key(Keycode::Kp3));
+        bindings.bind_action(VIEW_TOP, Binding::Key(Keycode::Kp7));
+        bindings.bind_action(RECORD_KEYFRAME, Binding::Key(Keycode::K));
+        bindings.bind_action(PLAY_CAMERA_PATH, Binding::Key(Keycode::L));
+        bindings.bind_action(QUIT, Binding::Key(Keycode::Escape));
+        bindings.bind_action(PAUSE, Binding::Key(Keycode::P));
+        bindings.bind_action(PAUSE, Binding::ControllerButton(Button::Start));
+        bindings.bind_action(SINGLE_STEP, Binding::Key(Keycode::Period));
+        bindings.bind_action(SLOWER, Binding::Key(Keycode::LeftBracket));
+        bindings.bind_action(FASTER, Binding::Key(Keycode::RightBracket));
+        bindings
+    }
+}
+
+// Tracks which bindings are held and answers queries about actions and axes by name
+#[derive(Clone, Debug, Default)]
+pub struct Input {
+    bindings: InputBindings,
+    // Keys and mouse buttons; gamepad buttons are kept per gamepad below
+    held: HashSet<Binding>,
+    // Per gamepad, keyed by joystick instance id, so unplugging one only releases its own inputs
+    controller_buttons: HashSet<(u32, Button)>,
+    // With the deadzone already applied
+    controller_axes: HashMap<(u32, Axis), f32>,
+    mouse_look_toggled: bool,
+    // Accumulated until taken, in pixels and wheel notches
+    mouse_motion: (f32, f32),
+    wheel: f32
+}
+
+impl Input {
+    pub fn new(bindings: InputBindings) -> Self {
+        Self {
+            bindings,
+            held: HashSet::new(),
+            controller_buttons: HashSet::new(),
+            controller_axes: HashMap::new(),
+            mouse_look_toggled: false,
+            mouse_motion: (0.0, 0.0),
+            wheel: 0.0
+        }
+    }
+
+    pub fn bindings(&self) -> &InputBindings {
+        &self.bindings
+    }
+
+    pub fn set_bindings(&mut self, bindings: InputBindings) {
+        self.bindings = bindings;
+    }
+
+    pub fn process_event(&mut self, event: &Event) {
+        if self.action_pressed(TOGGLE_MOUSE_LOOK, event) {
+            self.mouse_look_toggled = !self.mouse_look_toggled;
+        }
+
+        match *event {
+            Event::KeyDown { .. } | Event::MouseButtonDown { .. } => {
+                self.held.extend(event_bindings(event).into_iter().flatten());
+            },
+            Event::ControllerButtonDown { which, button, .. } => {
+                self.controller_buttons.insert((which, button));
+            },
+            Event::ControllerAxisMotion { which, axis, value, .. } => {
+                let value = apply_deadzone(value as f32 / i16::MAX as f32, self.bindings.controller_deadzone);
+                self.controller_axes.insert((which, axis), value);
+            },
+            Event::MouseMotion { xrel, yrel, .. } => {
+                self.mouse_motion.0 += xrel as f32;
+                self.mouse_motion.1 += yrel as f32;
+            },
+            Event::MouseWheel { precise_y, direction, .. } => {
+                self.wheel += match direction {
+                    MouseWheelDirection::Flipped => -precise_y,
+                    _ => precise_y
+                };
+            },
+            // Keys released while another window has focus never send a KeyUp
+            Event::Window { win_event: WindowEvent::FocusLost, .. } => {
+                self.held.clear();
+                self.controller_buttons.clear();
+            },
+            _ => self.process_release(event)
+        }
+    }
+
+    // Applies only releases, for events that were handled elsewhere but must not leave inputs stuck
+    pub fn process_release(&mut self, event: &Event) {
+        match *event {
+            Event::KeyUp { .. } | Event::MouseButtonUp { .. } => {
+                for binding in event_bindings(event).into_iter().flatten() {
+                    self.held.remove(&binding);
+                }
+            },
+            Event::ControllerButtonUp { which, button, .. } => {
+                self.controller_buttons.remove(&(which, button));
+            },
+            // Unplugging a gamepad releases everything on it, and nothing on the others
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.controller_axes.retain(|&(id, _), _| id != which);
+                self.controller_buttons.retain(|&(id, _)| id != which);
+            },
+            _ => {}
+        }
+    }
+
+    // True while the mouse_look action is held or after toggle_mouse_look turned it on; the app
+    // keeps SDL's relative mouse mode in step with this
+    pub fn mouse_look(&self) -> bool {
+        self.mouse_look_toggled || self.action_held(MOUSE_LOOK)
+    }
+
+    pub fn set_mouse_look(&mut self, mouse_look: bool) {
+        self.mouse_look_toggled = mouse_look;
+    }
+
+    // Mouse movement in pixels since the last call, right and down being positive
+    pub fn take_mouse_motion(&mut self) -> (f32, f32) {
+        std::mem::take(&mut self.mouse_motion)
+    }
+
+    // Wheel notches scrolled since the last call, away from the user being positive
+    pub fn take_wheel(&mut self) -> f32 {
+        std::mem::take(&mut self.wheel)
+    }
+
+    // Gamepad buttons count while held on any connected gamepad
+    pub fn is_held(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::ControllerButton(button) => self.controller_buttons.iter().any(|&(_, held)| held == button),
+            _ => self.held.contains(&binding)
+        }
+    }
+
+    pub fn action_held(&self, action: &str) -> bool {
+        self.bindings.actions
+            .get(action)
+            .is_some_and(|bindings| bindings.iter().any(|&binding| self.is_held(binding)))
+    }
+
+    // Whether event is a fresh press (not a key repeat) of any of the action's bindings
+    pub fn action_pressed(&self, action: &str, event: &Event) -> bool {
+        let pressed = match event {
+            Event::KeyDown { repeat: false, .. } | Event::MouseButtonDown { .. } | Event::ControllerButtonDown { .. } => event_bindings(event),
+            _ => return false
+        };
+        self.bindings.actions
+            .get(action)
+            .is_some_and(|bindings| pressed.into_iter().flatten().any(|binding| bindings.contains(&binding)))
+    }
+
+    // Summed over every connected gamepad
+    pub fn controller_axis(&self, axis: Axis) -> f32 {
+        self.controller_axes
+            .iter()
+            .filter(|((_, controller_axis), _)| *controller_axis == axis)
+            .map(|(_, value)| value)
+            .sum()
+    }
+
+    // Sum of the axis' bindings, clamped to -1..=1
+    pub fn axis(&self, axis: &str) -> f32 {
+        let Some(bindings) = self.bindings.axes.get(axis) else {
+            return 0.0;
+        };
+        let value: f32 = bindings
+            .iter()
+            .map(|binding| match *binding {
+                AxisBinding::Buttons { negative, positive } => self.is_held(positive) as i32 as f32 - self.is_held(negative) as i32 as f32,
+                AxisBinding::ControllerAxis { axis, scale } => self.controller_axis(axis) * scale
+            })
+            .sum();
+        value.clamp(-1.0, 1.0)
+    }
+}
+
+// A key event matches both the key's keycode and its scancode
+fn event_bindings(event: &Event) -> [Option<Binding>; 2] {
+    match *event {
+        Event::KeyDown { keycode, scancode, .. } |
+        Event::KeyUp { keycode, scancode, .. } => [keycode.map(Binding::Key), scancode.map(Binding::Scancode)],
+        Event::MouseButtonDown { mouse_btn, .. } |
+        Event::MouseButtonUp { mouse_btn, .. } => [Some(Binding::Mouse(mouse_btn)), None],
+        Event::ControllerButtonDown { button, .. } |
+        Event::ControllerButtonUp { button, .. } => [Some(Binding::ControllerButton(button)), None],
+        _ => [None, None]
+    }
+}
+
+// Rescales what is left past the deadzone to 0..=1, so the output still reaches full range smoothly
+fn apply_deadzone(value: f32, deadzone: f32) -> f32 {
+    let magnitude = value.abs().min(1.0);
+    if magnitude <= deadzone {
+        0.0
+    } else {
+        value.signum() * (magnitude - deadzone) / (1.0 - deadzone)
+    }
+}
+
+// Keys are stored by their SDL names, e.g. "W", "Left Shift" or "Keypad 8"
+mod keycode_name {
+    use sdl2::keyboard::Keycode;
+    use serde::{
+        de::Error,
+        Deserialize,
+        Deserializer,
+        Serializer
+    };
+
+    pub fn serialize<S: Serializer>(keycode: &Keycode, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&keycode.name())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Keycode, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Keycode::from_name(&name).ok_or_else(|| D::Error::custom(format!("unknown key {name:?}")))
+    }
+}
+
+mod scancode_name {
+    use sdl2::keyboard::Scancode;
+    use serde::{
+        de::Error,
+        Deserialize,
+        Deserializer,
+        Serializer
+    };
+
+    pub fn serialize<S: Serializer>(scancode: &Scancode, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(scancode.name())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Scancode, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Scancode::from_name(&name).ok_or_else(|| D::Error::custom(format!("unknown scancode {name:?}")))
+    }
+}
+
+mod mouse_button_name {
+    use sdl2::mouse::MouseButton;
+    use serde::{
+        de::Error,
+        Deserialize,
+        Deserializer,
+        Serializer
+    };
+
+    pub fn serialize<S: Serializer>(button: &MouseButton, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match button {
+            MouseButton::Left => "Left",
+            MouseButton::Middle => "Middle",
+            MouseButton::Right => "Right",
+            MouseButton::X1 => "X1",
+            MouseButton::X2 => "X2",
+            MouseButton::Unknown => "Unknown"
+        })
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<MouseButton, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        match name.as_str() {
+            "Left" => Ok(MouseButton::Left),
+            "Middle" => Ok(MouseButton::Middle),
+            "Right" => Ok(MouseButton::Right),
+            "X1" => Ok(MouseButton::X1),
+            "X2" => Ok(MouseButton::X2),
+            _ => Err(D::Error::custom(format!("unknown mouse button {name:?}")))
+        }
+    }
+}
+
+// Gamepad buttons and axes use SDL's mapping names, e.g. "a", "start", "dpup" or "leftx"
+mod controller_button_name {
+    use sdl2::controller::Button;
+    use serde::{
+        de::Error,
+        Deserialize,
+        Deserializer,
+        Serializer
+    };
+
+    pub fn serialize<S: Serializer>(button: &Button, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&button.string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Button, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Button::from_string(&name).ok_or_else(|| D::Error::custom(format!("unknown controller button {name:?}")))
+    }
+}
+
+mod controller_axis_name {
+    use sdl2::controller::Axis;
+    use serde::{
+        de::Error,
+        Deserialize,
+        Deserializer,
+        Serializer
+    };
+
+    pub fn serialize<S: Serializer>(axis: &Axis, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&axis.string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Axis, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Axis::from_string(&name).ok_or_else(|| D::Error::custom(format!("unknown controller axis {name:?}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sdl2::keyboard::Mod;
+
+    fn key_down(scancode: Scancode) -> Event {
+        Event::KeyDown {
+            timestamp: 0,
+            window_id: 0,
+            keycode: Keycode::from_scancode(scancode),
+            scancode: Some(scancode),
+            keymod: Mod::NOMOD,
+            repeat: false
+        }
+    }
+
+    fn controller_button(down: bool, which: u32, button: Button) -> Event {
+        if down {
+            Event::ControllerButtonDown { timestamp: 0, which, button }
+        } else {
+            Event::ControllerButtonUp { timestamp: 0, which, button }
+        }
+    }
+
+    #[test]
+    fn a_button_counts_while_held_on_any_gamepad() {
+        let mut input = Input::new(InputBindings::default());
+        input.process_event(&controller_button(true, 1, Button::Start));
+        input.process_event(&controller_button(true, 2, Button::Start));
+        input.process_event(&controller_button(false, 1, Button::Start));
+        assert!(input.action_held(PAUSE));
+        input.process_event(&controller_button(false, 2, Button::Start));
+        assert!(!input.action_held(PAUSE));
+    }
+
+    #[test]
+    fn unplugging_a_gamepad_only_releases_its_own_inputs() {
+        let mut input = Input::new(InputBindings::default());
+        input.process_event(&controller_button(true, 1, Button::LeftStick));
+        input.process_event(&controller_button(true, 2, Button::Y));
+        input.process_event(&Event::ControllerAxisMotion { timestamp: 0, which: 1, axis: Axis::LeftX, value: i16::MAX });
+        input.process_event(&Event::ControllerAxisMotion { timestamp: 0, which: 2, axis: Axis::LeftY, value: i16::MIN });
+
+        input.process_event(&Event::ControllerDeviceRemoved { timestamp: 0, which: 1 });
+        assert!(!input.action_held(SPRINT));
+        assert!(input.action_held(FRAME_SCENE));
+        assert_eq!(input.controller_axis(Axis::LeftX), 0.0);
+        assert_eq!(input.axis(MOVE_FORWARD), 1.0);
+    }
+
+    #[test]
+    fn the_bindings_file_matches_the_defaults() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("config/bindings.ron");
+        assert_eq!(InputBindings::load(path).unwrap(), InputBindings::default());
+    }
+
+    #[test]
+    fn bindings_round_trip_through_ron() {
+        let bindings = InputBindings::default();
+        let text = ron::ser::to_string_pretty(&bindings, ron::ser::PrettyConfig::default()).unwrap();
+        assert_eq!(ron::from_str::<InputBindings>(&text).unwrap(), bindings);
+    }
+
+    #[test]
+    fn opposite_buttons_cancel_out() {
+        let mut input = Input::new(InputBindings::default());
+        input.process_event(&key_down(Scancode::Space));
+        assert_eq!(input.axis(MOVE_UP), 1.0);
+        input.process_event(&key_down(Scancode::LShift));
+        assert_eq!(input.axis(MOVE_UP), 0.0);
+    }
+}
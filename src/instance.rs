@@ -0,0 +1,278 @@
+use std::ops::Range;
+use cgmath::{
+    Matrix,
+    Matrix4,
+    Matrix3,
+    SquareMatrix
+};
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct InstanceRaw {
+    transform_matrix: [[f32; 4]; 4],
+    normal: [[f32; 3]; 3]
+}
+
+impl InstanceRaw {
+    const BUFFER_LAYOUT_ATTRIBS: [wgpu::VertexAttribute; 7] = wgpu::vertex_attr_array![3 => Float32x4, 4 => Float32x4, 5 => Float32x4, 6 => Float32x4, 7 => Float32x3, 8 => Float32x3, 9 => Float32x3];
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<InstanceRaw>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::BUFFER_LAYOUT_ATTRIBS
+        }
+    }
+
+    pub fn from_matrix(transform_matrix: Matrix4<f32>) -> Self {
+        let linear = Matrix3::from_cols(
+            transform_matrix.x.truncate(),
+            transform_matrix.y.truncate(),
+            transform_matrix.z.truncate()
+        );
+        // Normals need the inverse transpose so non-uniform scale doesn't skew them
+        let normal = linear
+            .invert()
+            .map(|inverse| inverse.transpose())
+            .unwrap_or(linear);
+
+        Self {
+            transform_matrix: transform_matrix.into(),
+            normal: normal.into()
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct InstanceId {
+    slot: usize,
+    generation: u32
+}
+
+// Where a slot's instance lives in the packed data, if it is alive
+struct InstanceSlot {
+    generation: u32,
+    index: Option<usize>
+}
+
+// Densely packed instance data for one model, addressed through stable slot ids
+pub(crate) struct InstanceSet {
+    instances: Vec<InstanceRaw>,
+    index_slots: Vec<usize>,
+    slots: Vec<InstanceSlot>,
+    free_slots: Vec<usize>,
+    dirty: Option<Range<usize>>
+}
+
+impl InstanceSet {
+    pub fn new() -> Self {
+        Self {
+            instances: Vec::new(),
+            index_slots: Vec::new(),
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            dirty: None
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    // The packed index of a live instance, or None for a despawned one
+    fn index(&self, id: InstanceId) -> Option<usize> {
+        self.slots
+            .get(id.slot)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.index)
+    }
+
+    pub fn spawn(&mut self, instance: InstanceRaw) -> InstanceId {
+        let index = self.instances.len();
+        self.instances.push(instance);
+
+        let slot = match self.free_slots.pop() {
+            Some(slot) => slot,
+            None => {
+                self.slots.push(InstanceSlot { generation: 0, index: None });
+                self.slots.len() - 1
+            }
+        };
+        self.slots[slot].index = Some(index);
+        self.index_slots.push(slot);
+
+        self.mark_dirty(index);
+        InstanceId { slot, generation: self.slots[slot].generation }
+    }
+
+    // Stale ids, whose instance was already despawned, are ignored
+    pub fn despawn(&mut self, id: InstanceId) {
+        let Some(index) = self.index(id) else {
+            return;
+        };
+        let slot = &mut self.slots[id.slot];
+        slot.index = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_slots.push(id.slot);
+
+        // Keep the data dense by moving the last instance into the hole
+        self.instances.swap_remove(index);
+        self.index_slots.swap_remove(index);
+        if index < self.instances.len() {
+            self.slots[self.index_slots[index]].index = Some(index);
+            self.mark_dirty(index);
+        }
+
+        let len = self.instances.len();
+        self.dirty = self.dirty
+            .take()
+            .map(|dirty| dirty.start..dirty.end.min(len))
+            .filter(|dirty| !dirty.is_empty());
+    }
+
+    pub fn set(&mut self, id: InstanceId, instance: InstanceRaw) {
+        if let Some(index) = self.index(id) {
+            self.instances[index] = instance;
+            self.mark_dirty(index);
+        }
+    }
+
+    fn mark_dirty(&mut self, index: usize) {
+        self.dirty = Some(match self.dirty.take() {
+            Some(dirty) => dirty.start.min(index)..dirty.end.max(index + 1),
+            None => index..index + 1
+        });
+    }
+}
+
+pub(crate) struct InstanceBuffer {
+    buffer: Option<wgpu::Buffer>,
+    capacity: usize,
+    count: u32
+}
+
+impl InstanceBuffer {
+    const MIN_CAPACITY: usize = 16;
+    const STRIDE: usize = std::mem::size_of::<InstanceRaw>();
+
+    pub fn new() -> Self {
+        Self {
+            buffer: None,
+            capacity: 0,
+            count: 0
+        }
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn upload(&mut self, set: &mut InstanceSet, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let dirty = set.dirty.take();
+        self.count = set.len() as u32;
+
+        if set.len() > self.capacity {
+            self.capacity = (self.capacity * 2).max(set.len()).max(Self::MIN_CAPACITY);
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("instance_buffer"),
+                size: (self.capacity * Self::STRIDE) as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false
+            });
+            queue.write_buffer(&buffer, 0, bytemuck::cast_slice(&set.instances));
+            self.buffer = Some(buffer);
+        } else if let (Some(buffer), Some(dirty)) = (&self.buffer, dirty) {
+            let offset = (dirty.start * Self::STRIDE) as wgpu::BufferAddress;
+            queue.write_buffer(buffer, offset, bytemuck::cast_slice(&set.instances[dirty]));
+        }
+    }
+
+    pub fn slice(&self) -> Option<wgpu::BufferSlice<'_>> {
+        let buffer = self.buffer.as_ref().filter(|_| self.count > 0)?;
+        Some(buffer.slice(..(self.count as usize * Self::STRIDE) as wgpu::BufferAddress))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cgmath::Vector3;
+
+    fn instance(x: f32) -> InstanceRaw {
+        InstanceRaw::from_matrix(Matrix4::from_translation(Vector3::new(x, 0.0, 0.0)))
+    }
+
+    // The x translation of each instance, in buffer order
+    fn xs(set: &InstanceSet) -> Vec<f32> {
+        set.instances.iter().map(|instance| instance.transform_matrix[3][0]).collect()
+    }
+
+    #[test]
+    fn spawn_packs_instances_and_marks_them_dirty() {
+        let mut set = InstanceSet::new();
+        for x in [0.0, 1.0, 2.0] {
+            set.spawn(instance(x));
+        }
+        assert_eq!(xs(&set), [0.0, 1.0, 2.0]);
+        assert_eq!(set.dirty, Some(0..3));
+    }
+
+    #[test]
+    fn despawn_moves_the_last_instance_into_the_hole() {
+        let mut set = InstanceSet::new();
+        let ids = [0.0, 1.0, 2.0].map(|x| set.spawn(instance(x)));
+        set.dirty = None;
+
+        set.despawn(ids[0]);
+        assert_eq!(xs(&set), [2.0, 1.0]);
+        assert_eq!(set.dirty, Some(0..1));
+
+        // The moved instance is still reachable through its old id
+        set.set(ids[2], instance(5.0));
+        assert_eq!(xs(&set), [5.0, 1.0]);
+        set.set(ids[1], instance(6.0));
+        assert_eq!(xs(&set), [5.0, 6.0]);
+    }
+
+    #[test]
+    fn despawn_clamps_the_dirty_range() {
+        let mut set = InstanceSet::new();
+        let ids = [0.0, 1.0, 2.0].map(|x| set.spawn(instance(x)));
+        set.dirty = None;
+
+        set.set(ids[1], instance(4.0));
+        set.set(ids[2], instance(5.0));
+        set.despawn(ids[2]);
+        assert_eq!(set.dirty, Some(1..2));
+
+        // Nothing left in range to upload
+        set.despawn(ids[1]);
+        assert_eq!(set.dirty, None);
+        assert_eq!(xs(&set), [0.0]);
+    }
+
+    #[test]
+    fn despawned_slots_are_reused_and_stale_ids_ignored() {
+        let mut set = InstanceSet::new();
+        let first = set.spawn(instance(0.0));
+        let second = set.spawn(instance(1.0));
+
+        set.despawn(first);
+        set.despawn(first);
+        assert_eq!(set.len(), 1);
+
+        // The slot is reused under a new generation, so the old id cannot reach the new instance
+        let third = set.spawn(instance(2.0));
+        assert_ne!(third, first);
+        assert_eq!(third.slot, first.slot);
+        set.set(first, instance(8.0));
+        set.despawn(first);
+        assert_eq!(xs(&set), [1.0, 2.0]);
+
+        set.despawn(second);
+        set.set(second, instance(9.0));
+        assert_eq!(xs(&set), [2.0]);
+        set.set(third, instance(3.0));
+        assert_eq!(xs(&set), [3.0]);
+    }
+}
@@ -0,0 +1,79 @@
+use cgmath::{
+    Point3,
+    Vector3
+};
+
+pub const MAX_LIGHTS: usize = 8;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Light {
+    pub position: Point3<f32>,
+    pub color: Vector3<f32>
+}
+
+impl Light {
+    pub fn new<P, C>(position: P, color: C) -> Self
+    where
+        P: Into<Point3<f32>>,
+        C: Into<Vector3<f32>>
+    {
+        Self {
+            position: position.into(),
+            color: color.into()
+        }
+    }
+
+    fn to_raw(self) -> LightRaw {
+        LightRaw {
+            position: self.position.into(),
+            _padding0: 0,
+            color: self.color.into(),
+            _padding1: 0
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightRaw {
+    position: [f32; 3],
+    _padding0: u32,
+    color: [f32; 3],
+    _padding1: u32
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct LightsRaw {
+    lights: [LightRaw; MAX_LIGHTS],
+    count: u32,
+    _padding: [u32; 3]
+}
+
+impl LightsRaw {
+    // Only built when the lights change, so the warning is not repeated every frame
+    pub fn new(lights: &[Light]) -> Self {
+        if lights.len() > MAX_LIGHTS {
+            log::warn!("Only the first {MAX_LIGHTS} of {} lights are used, the rest have no effect", lights.len());
+        }
+
+        let mut raw: Self = bytemuck::Zeroable::zeroed();
+        for (raw_light, light) in raw.lights.iter_mut().zip(lights) {
+            *raw_light = light.to_raw();
+        }
+        raw.count = lights.len().min(MAX_LIGHTS) as u32;
+        raw
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lights_past_the_limit_are_left_out() {
+        let lights = vec![Light::new([0.0, 0.0, 0.0], [1.0, 1.0, 1.0]); MAX_LIGHTS + 2];
+        assert_eq!(LightsRaw::new(&lights).count, MAX_LIGHTS as u32);
+        assert_eq!(LightsRaw::new(&lights[..3]).count, 3);
+    }
+}
@@ -1,3 +1,218 @@
-fn main() {
-    pollster::block_on(window::run());
+use clap::{
+    Parser,
+    ValueEnum
+};
+use std::path::PathBuf;
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum PresentMode {
+    /// Vsync, using whichever of fifo-relaxed or fifo is available
+    AutoVsync,
+    /// No vsync, using whichever of immediate, mailbox or fifo is available
+    AutoNoVsync,
+    Fifo,
+    FifoRelaxed,
+    Mailbox,
+    Immediate
+}
+
+impl From<PresentMode> for wgpu::PresentMode {
+    fn from(mode: PresentMode) -> Self {
+        match mode {
+            PresentMode::AutoVsync => Self::AutoVsync,
+            PresentMode::AutoNoVsync => Self::AutoNoVsync,
+            PresentMode::Fifo => Self::Fifo,
+            PresentMode::FifoRelaxed => Self::FifoRelaxed,
+            PresentMode::Mailbox => Self::Mailbox,
+            PresentMode::Immediate => Self::Immediate
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, ValueEnum)]
+enum Backend {
+    /// Any available backend
+    All,
+    /// Vulkan, Metal, DX12 or WebGPU
+    Primary,
+    Vulkan,
+    Metal,
+    Dx12,
+    Gl
+}
+
+impl From<Backend> for wgpu::Backends {
+    fn from(backend: Backend) -> Self {
+        match backend {
+            Backend::All => Self::all(),
+            Backend::Primary => Self::PRIMARY,
+            Backend::Vulkan => Self::VULKAN,
+            Backend::Metal => Self::METAL,
+            Backend::Dx12 => Self::DX12,
+            Backend::Gl => Self::GL
+        }
+    }
+}
+
+/// Opens an OBJ model or a RON scene file in a window, or renders it to a PNG
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+    /// OBJ model or RON scene file to open; shows the teapot demo when omitted
+    path: Option<PathBuf>,
+
+    /// Window or output image width
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    width: Option<u32>,
+
+    /// Window or output image height
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    height: Option<u32>,
+
+    /// Open a borderless window at the desktop resolution
+    #[arg(long, conflicts_with = "output")]
+    fullscreen: bool,
+
+    /// How finished frames are presented to the window
+    #[arg(long, value_enum, default_value_t = PresentMode::AutoVsync)]
+    present_mode: PresentMode,
+
+    /// Cap the frame rate, on top of any vsync from the present mode
+    #[arg(long, value_name = "FPS", conflicts_with = "output")]
+    fps_limit: Option<u32>,
+
+    /// Graphics API to render with
+    #[arg(long, value_enum, default_value_t = Backend::All)]
+    backend: Backend,
+
+    /// MSAA sample count, overriding the scene file
+    #[arg(long, value_name = "SAMPLES")]
+    msaa: Option<u32>,
+
+    /// Render a single frame without opening a window and save it to this PNG; with --camera-path,
+    /// render the whole path as numbered PNGs into this directory
+    #[arg(long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Use the software adapter when rendering headless
+    #[arg(long, requires = "output")]
+    software: bool,
+
+    /// RON file mapping keys and mouse buttons to actions, see config/bindings.ron
+    #[arg(long, value_name = "FILE", conflicts_with = "output")]
+    bindings: Option<PathBuf>,
+
+    /// Mouse-look speed in radians per pixel
+    #[arg(long, value_name = "RADIANS", conflicts_with = "output")]
+    mouse_sensitivity: Option<f32>,
+
+    /// Look down when moving the mouse up
+    #[arg(long, conflicts_with = "output")]
+    invert_mouse_y: bool,
+
+    /// Make the mouse wheel zoom instead of changing the movement speed
+    #[arg(long, conflicts_with = "output")]
+    wheel_zoom: bool,
+
+    /// RON camera path to play back; recorded keyframes are saved to it, creating it if needed
+    #[arg(long, value_name = "FILE")]
+    camera_path: Option<PathBuf>,
+
+    /// Play the camera path as soon as the window opens
+    #[arg(long, requires = "camera_path", conflicts_with = "output")]
+    play: bool,
+
+    /// Frames per second of path time when rendering a camera path headless
+    #[arg(long, value_name = "FPS", default_value_t = 30, requires = "camera_path")]
+    path_fps: u32,
+
+    /// Extra directory to search for assets, before the default ones; can be repeated
+    #[arg(long = "asset-root", value_name = "DIR")]
+    asset_roots: Vec<PathBuf>
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
+    let args = Args::parse();
+
+    let mut window = window::WindowConfig {
+        fullscreen: args.fullscreen,
+        ..Default::default()
+    };
+    window.width = args.width.unwrap_or(window.width);
+    window.height = args.height.unwrap_or(window.height);
+
+    let mut fly_controller = window::FlyController::default();
+    if let Some(mouse_sensitivity) = args.mouse_sensitivity {
+        fly_controller.set_mouse_sensitivity(mouse_sensitivity);
+    }
+    fly_controller.set_invert_mouse_y(args.invert_mouse_y);
+    if args.wheel_zoom {
+        fly_controller.set_wheel_mode(window::WheelMode::Zoom);
+    }
+    let orbit_controller = window::OrbitController::default();
+
+    let mut builder = args.asset_roots
+        .into_iter()
+        .fold(window::AppBuilder::new(), window::AppBuilder::asset_root)
+        .window(window)
+        .backends(args.backend.into())
+        .present_mode(args.present_mode.into())
+        .target_fps(args.fps_limit);
+
+    let light = window::Light::new([2.0, 2.0, 2.0], [1.0, 1.0, 1.0]);
+    builder = match &args.path {
+        Some(path) if path.extension().is_some_and(|extension| extension == "ron") => builder
+            .scene_file(path)?
+            .camera_controller(fly_controller)
+            .camera_controller(orbit_controller),
+        // A single model is opened for inspection: orbiting it, framed to fit
+        Some(path) => {
+            // Asset roots do not include the working directory, so command-line models are made absolute
+            let path = path.canonicalize().map_err(|err| anyhow::anyhow!("Cannot open {}: {err}", path.display()))?;
+            builder
+                .model(path.to_string_lossy(), vec![window::Transform::default()])
+                .light(light)
+                .camera_controller(orbit_controller)
+                .camera_controller(fly_controller)
+                .auto_frame(true)
+        },
+        None => builder
+            .model("teapot.obj", vec![window::Transform::default()])
+            .light(light)
+            .camera_controller(fly_controller)
+            .camera_controller(orbit_controller)
+    };
+
+    if let Some(bindings) = &args.bindings {
+        builder = builder.input_bindings_file(bindings)?;
+    }
+
+    if let Some(msaa) = args.msaa {
+        builder = builder.msaa_samples(msaa);
+    }
+
+    if let Some(camera_path) = &args.camera_path {
+        builder = builder
+            .camera_path_file(camera_path)?
+            .play_camera_path(args.play);
+    }
+
+    match args.output {
+        Some(output) if args.camera_path.is_some() => {
+            let app = pollster::block_on(builder.build_headless(args.software))?;
+            app.run_headless_camera_path((), output, args.path_fps)?;
+        },
+        Some(output) => {
+            let app = pollster::block_on(builder.build_headless(args.software))?;
+            app.run_headless((), output)?;
+        },
+        None => {
+            let app = pollster::block_on(builder.build())?;
+            app.run(())?;
+        }
+    }
+
+    Ok(())
 }
@@ -1,23 +1,144 @@
 use crate::texture;
+use crate::bounds::{
+    Aabb,
+    BoundingSphere
+};
+use crate::assets::{
+    Assets,
+    Handle
+};
+use cgmath::Matrix4;
 use std::ops::Range;
+use wgpu::util::DeviceExt;
 
 pub struct Model {
     pub meshes: Vec<Mesh>,
-    pub materials: Vec<Material>
+    pub materials: Vec<Material>,
+    // In model space, computed from the meshes when the model is created
+    bounds: Option<Aabb>,
+    bounding_sphere: Option<BoundingSphere>
+}
+
+impl Model {
+    pub fn new(meshes: Vec<Mesh>, materials: Vec<Material>) -> Self {
+        let bounds = meshes
+            .iter()
+            .filter_map(Mesh::bounds)
+            .reduce(|bounds, mesh_bounds| bounds.union(&mesh_bounds));
+        // Meshes do not keep their vertices, so this encloses their spheres rather than every vertex
+        let bounding_sphere = meshes
+            .iter()
+            .filter_map(Mesh::bounding_sphere)
+            .reduce(|sphere, mesh_sphere| sphere.union(&mesh_sphere));
+        Self {
+            meshes,
+            materials,
+            bounds,
+            bounding_sphere
+        }
+    }
+
+    // None for a model without any vertices
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.bounds
+    }
+
+    pub fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        self.bounding_sphere
+    }
+
+    // The bounds of an instance placed by a world matrix
+    pub fn world_bounds(&self, world: &Matrix4<f32>) -> Option<Aabb> {
+        Some(self.bounds?.transform(world))
+    }
+
+    pub fn world_bounding_sphere(&self, world: &Matrix4<f32>) -> Option<BoundingSphere> {
+        Some(self.bounding_sphere?.transform(world))
+    }
 }
 
 pub struct Material {
     pub name: String,
-    pub texture: texture::Texture,
+    pub texture: Handle<texture::Texture>,
     pub bind_group: wgpu::BindGroup
 }
 
+impl Material {
+    pub fn new(name: String, texture: Handle<texture::Texture>, textures: &Assets<texture::Texture>, layout: &wgpu::BindGroupLayout, device: &wgpu::Device) -> Self {
+        let bind_group = Self::create_bind_group(textures.get(&texture), layout, device);
+        Self {
+            name,
+            texture,
+            bind_group
+        }
+    }
+
+    fn create_bind_group(texture: &texture::Texture, layout: &wgpu::BindGroupLayout, device: &wgpu::Device) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+            label: None,
+        })
+    }
+}
+
 pub struct Mesh {
     pub name: String,
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: wgpu::Buffer,
     pub num_elements: u32,
-    pub material: usize
+    pub material: usize,
+    // Computed once here since culling checks them every frame
+    bounds: Option<Aabb>,
+    bounding_sphere: Option<BoundingSphere>
+}
+
+impl Mesh {
+    pub fn new(name: String, vertices: &[ModelVertex], indices: &[u32], material: usize, device: &wgpu::Device) -> Self {
+        let (vertex_buffer, index_buffer) = Self::create_buffers(&name, vertices, indices, device);
+        let bounds = Aabb::from_points(vertices.iter().map(|vertex| vertex.position.into()));
+        let bounding_sphere = BoundingSphere::from_points(vertices.iter().map(|vertex| vertex.position.into()));
+        Self {
+            name,
+            vertex_buffer,
+            index_buffer,
+            num_elements: indices.len() as u32,
+            material,
+            bounds,
+            bounding_sphere
+        }
+    }
+
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.bounds
+    }
+
+    pub fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        self.bounding_sphere
+    }
+
+    fn create_buffers(name: &str, vertices: &[ModelVertex], indices: &[u32], device: &wgpu::Device) -> (wgpu::Buffer, wgpu::Buffer) {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{:?} Vertex Buffer", name)),
+            contents: bytemuck::cast_slice(vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{:?} Index Buffer", name)),
+            contents: bytemuck::cast_slice(indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        (vertex_buffer, index_buffer)
+    }
 }
 
 pub trait Vertex {
@@ -44,26 +165,18 @@ impl Vertex for ModelVertex {
     }
 }
 
+// Material bind groups are set by the caller so draws sharing a material can skip rebinding
 pub trait DrawModel<'a> {
-    fn draw_mesh(&mut self, mesh: &'a Mesh, materal: &'a Material, instances: Range<u32>);
-    fn draw_model(&mut self, model: &'a Model, instances: Range<u32>);
+    fn draw_mesh(&mut self, mesh: &'a Mesh, instances: Range<u32>);
 }
 
 impl<'a, 'b> DrawModel<'b> for wgpu::RenderPass<'a>
 where
     'b: 'a
 {
-    fn draw_mesh(&mut self, mesh: &'b Mesh, material: &'b Material, instances: Range<u32>) {
+    fn draw_mesh(&mut self, mesh: &'b Mesh, instances: Range<u32>) {
         self.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
         self.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-        self.set_bind_group(0, &material.bind_group, &[]);
         self.draw_indexed(0..mesh.num_elements, 0, instances)
     }
-
-    fn draw_model(&mut self, model: &'b Model, instances: Range<u32>) {
-        for mesh in &model.meshes {
-            let material = &model.materials[mesh.material];
-            self.draw_mesh(mesh, material, instances.clone());
-        }
-    }
 }
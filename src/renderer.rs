@@ -0,0 +1,779 @@
+use crate::{
+    camera,
+    model,
+    resources,
+    texture
+};
+use crate::vfs::AssetRoots;
+use crate::error::{
+    Error,
+    Result
+};
+use crate::instance::{
+    InstanceRaw,
+    InstanceBuffer
+};
+use crate::scene::{
+    NodeId,
+    Scene
+};
+use crate::bounds::{
+    Aabb,
+    BoundingSphere,
+    Frustum,
+    Ray
+};
+use crate::assets::{
+    AssetId,
+    AssetServer,
+    Handle,
+    MissingAssetPolicy
+};
+use crate::light::{
+    Light,
+    LightsRaw
+};
+use crate::target::{
+    RenderTarget,
+    WindowTarget,
+    OffscreenTarget
+};
+use model::Vertex;
+use model::DrawModel;
+
+use wgpu::util::DeviceExt;
+use std::{
+    collections::{
+        HashMap,
+        HashSet
+    },
+    path::Path
+};
+
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct RenderSettings {
+    pub clear_color: [f64; 4],
+    // Ignored with a warning when the adapter cannot rasterize lines
+    pub wireframe: bool,
+    // Falls back to 1 when the adapter does not support the requested count
+    pub msaa_samples: u32
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            clear_color: [0.5, 0.5, 0.5, 1.0],
+            wireframe: false,
+            msaa_samples: 1
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct DrawItem {
+    model: AssetId,
+    material: usize,
+    mesh: usize
+}
+
+pub struct Renderer {
+    target: RenderTarget,
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    assets: AssetServer,
+    asset_roots: AssetRoots,
+    missing_asset_policy: MissingAssetPolicy,
+    instance_buffers: HashMap<AssetId, InstanceBuffer>,
+    draw_list: Vec<DrawItem>,
+    draw_list_dirty: bool,
+    // Models with at least one instance in view this frame; the rest are skipped when drawing
+    visible_models: HashSet<AssetId>,
+    scene: Scene,
+    settings: RenderSettings,
+    depth_texture: texture::Texture,
+    msaa_view: Option<wgpu::TextureView>,
+    camera: camera::Camera,
+    // The camera at the start of the current simulation step, for render interpolation
+    previous_camera: camera::Camera,
+    interpolation_alpha: f32,
+    camera_proj: camera::CameraProjection,
+    camera_proj_raw: camera::CameraProjectionRaw,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    lights: Vec<Light>,
+    lights_dirty: bool,
+    light_buffer: wgpu::Buffer,
+    light_bind_group: wgpu::BindGroup,
+    shader: wgpu::ShaderModule,
+    pipeline_layout: wgpu::PipelineLayout,
+    render_pipeline: wgpu::RenderPipeline
+}
+
+impl Renderer {
+    pub(crate) async fn new_windowed(window: sdl2::video::Window, backends: wgpu::Backends, present_mode: wgpu::PresentMode, camera: camera::Camera) -> Result<Self> {
+        // In pixels, which differs from the window size in points on HiDPI displays
+        let (window_width, window_height) = window.vulkan_drawable_size();
+
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends,
+            ..Default::default()
+        });
+
+        let surface = unsafe { instance.create_surface(&window) }?;
+
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::LowPower,
+                force_fallback_adapter: false,
+                compatible_surface: Some(&surface)
+            })
+            .await
+            .ok_or(Error::AdapterUnavailable { force_fallback_adapter: false })?;
+
+        let (device, queue) = Self::request_device(&adapter).await?;
+
+        let surface_caps = surface.get_capabilities(&adapter);
+
+        let texture_format = surface_caps
+            .formats
+            .iter()
+            .copied()
+            .find(|f| f.is_srgb())
+            .unwrap_or(surface_caps.formats[0]);
+
+        let present_mode = WindowTarget::select_present_mode(present_mode, &surface_caps.present_modes);
+
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: texture_format,
+            width: window_width,
+            height: window_height,
+            present_mode,
+            alpha_mode: surface_caps.alpha_modes[0],
+            view_formats: vec![]
+        };
+        surface.configure(&device, &surface_config);
+
+        let target = RenderTarget::Window(WindowTarget {
+            window,
+            surface,
+            surface_config
+        });
+
+        Ok(Self::with_target(target, adapter, device, queue, camera))
+    }
+
+    pub(crate) async fn new_headless(width: u32, height: u32, backends: wgpu::Backends, force_fallback_adapter: bool, camera: camera::Camera) -> Result<Self> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends,
+            ..Default::default()
+        });
+
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::LowPower,
+                force_fallback_adapter,
+                compatible_surface: None
+            })
+            .await
+            .ok_or(Error::AdapterUnavailable { force_fallback_adapter })?;
+
+        let (device, queue) = Self::request_device(&adapter).await?;
+
+        let target = RenderTarget::Offscreen(OffscreenTarget::new(width, height, &device));
+
+        Ok(Self::with_target(target, adapter, device, queue, camera))
+    }
+
+    async fn request_device(adapter: &wgpu::Adapter) -> Result<(wgpu::Device, wgpu::Queue)> {
+        let device_and_queue = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: Some("device"),
+                // Software adapters may not support line rasterization; the adapter specific
+                // format features allow MSAA sample counts other than 4
+                features: adapter.features() & (wgpu::Features::POLYGON_MODE_LINE | wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES),
+                limits: wgpu::Limits::default()
+            }, None)
+            .await?;
+        Ok(device_and_queue)
+    }
+
+    fn with_target(target: RenderTarget, adapter: wgpu::Adapter, device: wgpu::Device, queue: wgpu::Queue, camera: camera::Camera) -> Self {
+        let (target_width, target_height) = target.size();
+        let texture_format = target.format();
+
+        let texture_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("texture_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false
+                    },
+                    count: None
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None
+                }
+            ]
+        });
+
+        let settings = RenderSettings::default();
+        let depth_texture = texture::Texture::new_depth_texture(target_width, target_height, settings.msaa_samples, &device);
+
+        let camera_proj = camera::CameraProjection::new(
+            target_width as f32,
+            target_height as f32,
+            0.1,
+            100.0
+        );
+        let mut camera_proj_raw = camera::CameraProjectionRaw::new();
+        camera_proj_raw.update_proj_matrix(&camera_proj, &camera);
+
+        let camera_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("camera_buffer"),
+            contents: bytemuck::cast_slice(&[camera_proj_raw]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+        });
+
+        let camera_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("camera_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None
+                },
+                count: None
+            }]
+        });
+
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("camera_bind_group"),
+            layout: &camera_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding()
+            }]
+        });
+
+        let light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("light_buffer"),
+            contents: bytemuck::cast_slice(&[LightsRaw::new(&[])]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST
+        });
+
+        let light_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("light_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None
+                },
+                count: None
+            }]
+        });
+
+        let light_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("light_bind_group"),
+            layout: &light_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: light_buffer.as_entire_binding()
+            }]
+        });
+
+        // -----------------------------------------
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("shader.wgsl"));
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("pipeline_layout"),
+            bind_group_layouts: &[
+                &texture_bind_group_layout,
+                &camera_bind_group_layout,
+                &light_bind_group_layout
+            ],
+            push_constant_ranges: &[]
+        });
+
+        let render_pipeline = Self::create_render_pipeline(&device, &pipeline_layout, &shader, texture_format, &settings);
+
+        Self {
+            target,
+            adapter,
+            device,
+            queue,
+            texture_bind_group_layout,
+            assets: AssetServer::new(),
+            asset_roots: AssetRoots::default(),
+            missing_asset_policy: MissingAssetPolicy::default(),
+            instance_buffers: HashMap::new(),
+            draw_list: Vec::new(),
+            draw_list_dirty: false,
+            visible_models: HashSet::new(),
+            scene: Scene::new(),
+            settings,
+            depth_texture,
+            msaa_view: None,
+            camera,
+            previous_camera: camera,
+            interpolation_alpha: 1.0,
+            camera_proj,
+            camera_proj_raw,
+            camera_buffer,
+            camera_bind_group,
+            lights: Vec::new(),
+            lights_dirty: true,
+            light_buffer,
+            light_bind_group,
+            shader,
+            pipeline_layout,
+            render_pipeline
+        }
+    }
+
+    fn create_render_pipeline(device: &wgpu::Device, layout: &wgpu::PipelineLayout, shader: &wgpu::ShaderModule, texture_format: wgpu::TextureFormat, settings: &RenderSettings) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("render_pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[model::ModelVertex::desc(), InstanceRaw::desc()]
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: if settings.wireframe { wgpu::PolygonMode::Line } else { wgpu::PolygonMode::Fill },
+                conservative: false
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: texture::Texture::DEPTH_TEXTURE_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default()
+            }),
+            multisample: wgpu::MultisampleState {
+                count: settings.msaa_samples,
+                mask: !0,
+                alpha_to_coverage_enabled: false
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: texture_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::all()
+                })]
+            }),
+            multiview: None
+        })
+    }
+
+    fn create_msaa_view(&self) -> Option<wgpu::TextureView> {
+        if self.settings.msaa_samples <= 1 {
+            return None;
+        }
+
+        let (width, height) = self.target.size();
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("msaa_texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1
+            },
+            mip_level_count: 1,
+            sample_count: self.settings.msaa_samples,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.target.format(),
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[]
+        });
+        Some(texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("msaa_texture_view"),
+            ..Default::default()
+        }))
+    }
+
+    fn recreate_attachments(&mut self) {
+        let (width, height) = self.target.size();
+        self.depth_texture = texture::Texture::new_depth_texture(width, height, self.settings.msaa_samples, &self.device);
+        self.msaa_view = self.create_msaa_view();
+    }
+
+    fn supported_sample_count(&self, requested: u32) -> u32 {
+        let color_flags = self.adapter.get_texture_format_features(self.target.format()).flags;
+        let depth_flags = self.adapter.get_texture_format_features(texture::Texture::DEPTH_TEXTURE_FORMAT).flags;
+        if requested <= 1 {
+            1
+        } else if color_flags.sample_count_supported(requested)
+            && color_flags.contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE)
+            && depth_flags.sample_count_supported(requested)
+        {
+            requested
+        } else {
+            log::warn!("{requested}x MSAA is not supported by this adapter, rendering without it");
+            1
+        }
+    }
+
+    pub fn render_settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    pub fn set_render_settings(&mut self, mut settings: RenderSettings) {
+        settings.msaa_samples = self.supported_sample_count(settings.msaa_samples);
+        if settings.wireframe && !self.device.features().contains(wgpu::Features::POLYGON_MODE_LINE) {
+            log::warn!("Wireframe rendering is not supported by this adapter");
+            settings.wireframe = false;
+        }
+
+        let rebuild_pipeline = settings.wireframe != self.settings.wireframe || settings.msaa_samples != self.settings.msaa_samples;
+        self.settings = settings;
+        if rebuild_pipeline {
+            self.render_pipeline = Self::create_render_pipeline(&self.device, &self.pipeline_layout, &self.shader, self.target.format(), &self.settings);
+            self.recreate_attachments();
+        }
+    }
+
+    pub fn load_model<P: AsRef<Path>>(&mut self, filename: P) -> Result<Handle<model::Model>> {
+        let assets = &mut self.assets;
+        assets.models.get_or_load(self.asset_roots.resolve(filename)?, |path| {
+            resources::load_model(path, &self.asset_roots, self.missing_asset_policy, &mut assets.textures, &self.texture_bind_group_layout, &self.device, &self.queue)
+        })
+    }
+
+    pub fn asset_roots(&self) -> &AssetRoots {
+        &self.asset_roots
+    }
+
+    pub fn set_asset_roots(&mut self, asset_roots: AssetRoots) {
+        self.asset_roots = asset_roots;
+    }
+
+    pub fn missing_asset_policy(&self) -> MissingAssetPolicy {
+        self.missing_asset_policy
+    }
+
+    // Applies to models loaded from now on
+    pub fn set_missing_asset_policy(&mut self, policy: MissingAssetPolicy) {
+        self.missing_asset_policy = policy;
+    }
+
+    pub fn assets(&self) -> &AssetServer {
+        &self.assets
+    }
+
+    pub fn assets_mut(&mut self) -> &mut AssetServer {
+        &mut self.assets
+    }
+
+    // Draws are ordered so that meshes sharing a material and instance buffer are submitted back to back
+    fn rebuild_draw_list(&mut self) {
+        let models = &self.assets.models;
+        self.draw_list = self.instance_buffers
+            .keys()
+            .filter_map(|&model| models.get_by_id(model).map(|loaded| (model, loaded)))
+            .flat_map(|(model, loaded)| loaded.meshes.iter().enumerate().map(move |(mesh, m)| DrawItem {
+                model,
+                material: m.material,
+                mesh
+            }))
+            .collect();
+        self.draw_list.sort_unstable();
+        self.draw_list_dirty = false;
+    }
+
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    pub fn scene_mut(&mut self) -> &mut Scene {
+        &mut self.scene
+    }
+
+    // Only the first MAX_LIGHTS are lit; a warning is logged when there are more
+    pub fn add_light(&mut self, light: Light) {
+        self.lights.push(light);
+        self.lights_dirty = true;
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    pub fn lights_mut(&mut self) -> &mut Vec<Light> {
+        self.lights_dirty = true;
+        &mut self.lights
+    }
+
+    pub fn camera(&self) -> &camera::Camera {
+        &self.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut camera::Camera {
+        &mut self.camera
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.target.size()
+    }
+
+    // World space bounds of every model in the scene, or None if there are none
+    pub fn scene_bounds(&mut self) -> Option<Aabb> {
+        self.scene.update_world_matrices();
+        self.scene
+            .model_nodes()
+            .filter_map(|(_, model, world)| self.assets.models.get(model).world_bounds(&world))
+            .reduce(|bounds, model_bounds| bounds.union(&model_bounds))
+    }
+
+    // World space bounds of a node's model, or None if it has none
+    pub fn node_bounds(&mut self, node: NodeId) -> Option<Aabb> {
+        self.scene.update_world_matrices();
+        let model = self.assets.models.get(self.scene.model(node)?);
+        model.world_bounds(&self.scene.world_matrix(node))
+    }
+
+    pub fn node_bounding_sphere(&mut self, node: NodeId) -> Option<BoundingSphere> {
+        self.scene.update_world_matrices();
+        let model = self.assets.models.get(self.scene.model(node)?);
+        model.world_bounding_sphere(&self.scene.world_matrix(node))
+    }
+
+    pub fn projection_mode(&self) -> camera::ProjectionMode {
+        self.camera_proj.mode()
+    }
+
+    pub fn set_projection_mode(&mut self, mode: camera::ProjectionMode) {
+        self.camera_proj.set_mode(mode);
+    }
+
+    pub fn view_frustum(&self) -> Frustum {
+        self.camera_proj.frustum(&self.camera)
+    }
+
+    // The ray through a pixel of the target, from its top left corner
+    pub fn pick_ray(&self, x: f32, y: f32) -> Option<Ray> {
+        let (width, height) = self.target.size();
+        self.camera_proj.pixel_ray(&self.camera, x, y, width, height)
+    }
+
+    // The closest node whose model's bounding box is under the pixel
+    pub fn pick(&mut self, x: f32, y: f32) -> Option<NodeId> {
+        let ray = self.pick_ray(x, y)?;
+        self.scene.update_world_matrices();
+        self.scene
+            .model_nodes()
+            .filter_map(|(node, model, world)| {
+                let model = self.assets.models.get(model);
+                // The sphere is quicker to test, and rules out most misses
+                ray.intersect_sphere(&model.world_bounding_sphere(&world)?)?;
+                Some((node, ray.intersect_aabb(&model.world_bounds(&world)?)?))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(node, _)| node)
+    }
+
+    // Matches the target to the window's current drawable size
+    pub fn resize_to_window(&mut self) {
+        if let RenderTarget::Window(target) = &self.target {
+            let (width, height) = target.window.vulkan_drawable_size();
+            self.resize(width, height);
+        }
+    }
+
+    // None for offscreen targets, which are never presented
+    pub fn present_mode(&self) -> Option<wgpu::PresentMode> {
+        match &self.target {
+            RenderTarget::Window(target) => Some(target.surface_config.present_mode),
+            RenderTarget::Offscreen(_) => None
+        }
+    }
+
+    // Returns the mode actually used, which differs from the requested one if it is unsupported
+    pub fn set_present_mode(&mut self, present_mode: wgpu::PresentMode) -> Option<wgpu::PresentMode> {
+        match &mut self.target {
+            RenderTarget::Window(target) => Some(target.set_present_mode(present_mode, &self.adapter, &self.device)),
+            RenderTarget::Offscreen(_) => None
+        }
+    }
+
+    // Sizes are in pixels; zero sizes, as reported while minimized, are ignored since wgpu rejects them
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 || (width, height) == self.target.size() {
+            return;
+        }
+        self.target.resize(width, height, &self.device);
+
+        self.camera_proj.resize(width as f32, height as f32);
+        self.recreate_attachments();
+    }
+
+    // Snapshots the scene and camera so frames rendered before the next step can interpolate from them
+    pub(crate) fn begin_step(&mut self) {
+        self.scene.begin_step();
+        self.previous_camera = self.camera;
+    }
+
+    // Makes the current camera the starting point of interpolation too, so a jump is not animated
+    pub(crate) fn snap_camera(&mut self) {
+        self.previous_camera = self.camera;
+    }
+
+    pub(crate) fn set_interpolation_alpha(&mut self, alpha: f32) {
+        self.interpolation_alpha = alpha;
+    }
+
+    fn prepare(&mut self) {
+        self.scene.update_world_matrices();
+        self.scene.interpolate(self.interpolation_alpha);
+        for (model, instance_set) in self.scene.instance_sets_mut() {
+            let instance_buffer = self.instance_buffers.entry(model).or_insert_with(|| {
+                self.draw_list_dirty = true;
+                InstanceBuffer::new()
+            });
+            instance_buffer.upload(instance_set, &self.device, &self.queue);
+        }
+
+        let buffer_count = self.instance_buffers.len();
+        let scene = &self.scene;
+        self.instance_buffers.retain(|&model, _| scene.has_instances(model));
+        self.draw_list_dirty |= self.instance_buffers.len() != buffer_count;
+
+        self.assets.collect_garbage();
+        if self.draw_list_dirty {
+            self.rebuild_draw_list();
+        }
+
+        if self.lights_dirty {
+            self.queue.write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&[LightsRaw::new(&self.lights)]));
+            self.lights_dirty = false;
+        }
+
+        let camera = self.previous_camera.lerp(&self.camera, self.interpolation_alpha);
+        self.camera_proj_raw.update_proj_matrix(&self.camera_proj, &camera);
+
+        // Per model rather than per instance, since instances are drawn in a single call. Culled
+        // with the same interpolated camera and matrices that are drawn, so nothing pops at the edges
+        let frustum = self.camera_proj.frustum(&camera);
+        let models = &self.assets.models;
+        self.visible_models = self.scene
+            .drawn_model_nodes()
+            .filter(|(_, model, world)| {
+                let model = models.get(model);
+                let Some(sphere) = model.world_bounding_sphere(world) else {
+                    return false;
+                };
+                // The box is only built and checked when the sphere is in view, as it is tighter but slower
+                frustum.intersects_sphere(&sphere)
+                    && model.world_bounds(world).is_some_and(|bounds| frustum.intersects_aabb(&bounds))
+            })
+            .map(|(_, model, _)| model.id())
+            .collect();
+        self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_proj_raw]));
+    }
+
+    // Brings the surface back in line with the window after a Lost or Outdated error
+    pub fn reconfigure_surface(&mut self) {
+        let (width, height) = self.target.size();
+        self.target.resize(width, height, &self.device);
+    }
+
+    pub fn render(&mut self) -> Result<()> {
+        self.prepare();
+
+        let frame = self.target.acquire_frame()?;
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("command_encoder") });
+        let [r, g, b, a] = self.settings.clear_color;
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("render_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: self.msaa_view.as_ref().unwrap_or(&frame.view),
+                    resolve_target: self.msaa_view.as_ref().map(|_| &frame.view),
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color { r, g, b, a }),
+                        store: wgpu::StoreOp::Store
+                    }
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store
+                    }),
+                    stencil_ops: None
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None
+            });
+            render_pass.set_pipeline(&self.render_pipeline);
+            render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
+            render_pass.set_bind_group(2, &self.light_bind_group, &[]);
+
+            let mut bound_model = None;
+            let mut bound_material = None;
+            for item in &self.draw_list {
+                let (Some(model), Some(instance_buffer)) = (self.assets.models.get_by_id(item.model), self.instance_buffers.get(&item.model)) else {
+                    continue;
+                };
+                let Some(instance_slice) = instance_buffer.slice() else {
+                    continue;
+                };
+                if !self.visible_models.contains(&item.model) {
+                    continue;
+                }
+                if bound_model != Some(item.model) {
+                    render_pass.set_vertex_buffer(1, instance_slice);
+                    bound_model = Some(item.model);
+                    bound_material = None;
+                }
+                if bound_material != Some(item.material) {
+                    render_pass.set_bind_group(0, &model.materials[item.material].bind_group, &[]);
+                    bound_material = Some(item.material);
+                }
+                render_pass.draw_mesh(&model.meshes[item.mesh], 0..instance_buffer.count());
+            }
+        }
+
+        if let RenderTarget::Offscreen(target) = &self.target {
+            target.copy_to_readback(&mut encoder);
+        }
+
+        self.queue.submit([encoder.finish()]);
+        frame.present();
+
+        Ok(())
+    }
+
+    pub fn save_frame<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        match &self.target {
+            RenderTarget::Offscreen(target) => target.save_png(path, &self.device),
+            RenderTarget::Window(_) => Err(Error::ReadbackUnsupported)
+        }
+    }
+}
@@ -1,80 +1,137 @@
-use std::io::{BufReader, Cursor};
-use std::path::{Path, PathBuf};
-use std::fs;
-use wgpu::util::DeviceExt;
+use std::cell::RefCell;
+use std::io::Cursor;
 
 use crate::{model, texture};
+use crate::assets::{Assets, Handle, MissingAssetPolicy};
+use crate::error::{Error, Result};
+use crate::vfs::{AssetPath, AssetRoots};
 
-fn load_path(filename: &str) -> PathBuf {
-    Path::new(env!("OUT_DIR"))
-        .join("res")
-        .join(filename)
+const MISSING_TEXTURE: &str = "missing_texture.png";
+const MISSING_MATERIAL: &str = "missing_material";
+
+// The error followed by its causes, for log messages
+fn describe(err: &Error) -> String {
+    let mut message = err.to_string();
+    let mut source = std::error::Error::source(err);
+    while let Some(cause) = source {
+        message.push_str(&format!(": {cause}"));
+        source = cause.source();
+    }
+    message
+}
+
+fn read_asset(path: &AssetPath) -> Result<String> {
+    path.read_to_string().map_err(|source| Error::Io { path: path.to_path_buf(), source })
+}
+
+// tobj reads one line at a time, so the cursor sits just past the line that failed to parse
+fn parse_error(path: &AssetPath, text: &str, cursor: &Cursor<&[u8]>, source: tobj::LoadError) -> Error {
+    let consumed = &text.as_bytes()[..cursor.position() as usize];
+    let newlines = consumed.iter().filter(|&&byte| byte == b'\n').count();
+    let line = if consumed.ends_with(b"\n") { newlines } else { newlines + 1 };
+    Error::Parse { path: path.to_path_buf(), line: line.max(1), source }
 }
 
-fn load_texture(filename: &str, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<texture::Texture> {
-    let data = fs::read(load_path(filename))?;
-    Ok(texture::Texture::from_image_bytes(&data, filename, device, queue))
+fn load_texture(filename: &str, referrer: &AssetPath, roots: &AssetRoots, textures: &mut Assets<texture::Texture>, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<Handle<texture::Texture>> {
+    textures.get_or_load(roots.resolve_near(filename, Some(referrer))?, |path| {
+        let data = path.read().map_err(|source| Error::Io { path: path.to_path_buf(), source })?;
+        texture::Texture::from_image_bytes(&data, filename, device, queue)
+            .map_err(|source| Error::Decode { path: path.to_path_buf(), source })
+    })
 }
 
-pub fn load_model(filename: &str, bind_group_layout: &wgpu::BindGroupLayout, device: &wgpu::Device, queue: &wgpu::Queue) -> anyhow::Result<model::Model> {
-    let obj_text = fs::read_to_string(load_path(filename))?;
-    let obj_cursor = Cursor::new(obj_text);
-    let mut obj_reader = BufReader::new(obj_cursor);
+// Shared by every material that needs it; generated if the file itself is unavailable
+fn missing_texture(roots: &AssetRoots, textures: &mut Assets<texture::Texture>, device: &wgpu::Device, queue: &wgpu::Queue) -> Handle<texture::Texture> {
+    roots.resolve(MISSING_TEXTURE)
+        .and_then(|path| textures.get_or_load(path, |path| {
+            let data = path.read().map_err(|source| Error::Io { path: path.to_path_buf(), source })?;
+            texture::Texture::from_image_bytes(&data, MISSING_TEXTURE, device, queue)
+                .map_err(|source| Error::Decode { path: path.to_path_buf(), source })
+        }))
+        .unwrap_or_else(|err| {
+            log::warn!("{}, generating a checkerboard instead", describe(&err));
+            textures.add(texture::Texture::checkerboard(MISSING_TEXTURE, device, queue))
+        })
+}
 
+pub fn load_model(path: &AssetPath, roots: &AssetRoots, policy: MissingAssetPolicy, textures: &mut Assets<texture::Texture>, bind_group_layout: &wgpu::BindGroupLayout, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<model::Model> {
+    let filename = path.file_name().unwrap_or_default();
+    let obj_text = read_asset(path)?;
+    let mut obj_cursor = Cursor::new(obj_text.as_bytes());
+
+    // The material loader can only report a tobj error, so the detailed one is kept here
+    let mtl_error = RefCell::new(None);
     let (models, obj_materials) = tobj::load_obj_buf(
-        &mut obj_reader,
+        &mut obj_cursor,
         &tobj::LoadOptions {
             triangulate: true,
             single_index: true,
             ..Default::default()
         },
         |p| {
-            let mat_text = fs::read_to_string(load_path(p.to_str().unwrap())).unwrap();
-            tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text)))
+            let loaded = roots
+                .resolve_near(p, Some(path))
+                .and_then(|mat_path| {
+                    let mat_text = read_asset(&mat_path)?;
+                    let mut mat_cursor = Cursor::new(mat_text.as_bytes());
+                    tobj::load_mtl_buf(&mut mat_cursor)
+                        .map_err(|source| parse_error(&mat_path, &mat_text, &mat_cursor, source))
+                });
+            loaded.map_err(|err| {
+                let source = match &err {
+                    Error::Parse { source, .. } => *source,
+                    _ => tobj::LoadError::OpenFileFailed
+                };
+                *mtl_error.borrow_mut() = Some(err);
+                source
+            })
         },
-    )?;
-    let mut obj_materials = obj_materials?;
+    ).map_err(|source| parse_error(path, &obj_text, &obj_cursor, source))?;
+    let mut obj_materials = match obj_materials {
+        Ok(obj_materials) => obj_materials,
+        Err(source) => {
+            let err = mtl_error.take().unwrap_or_else(|| parse_error(path, &obj_text, &obj_cursor, source));
+            if policy == MissingAssetPolicy::Error {
+                return Err(err);
+            }
+            log::warn!("{}, {path} will use the missing material", describe(&err));
+            Vec::new()
+        }
+    };
 
-    if obj_materials.len() == 0 {
+    // Meshes without a usable material id get the missing material, added after the file's own
+    let material_count = obj_materials.len();
+    let material_index = |m: &tobj::Model| m.mesh.material_id.filter(|&id| id < material_count);
+    let missing_material = material_count;
+    if models.iter().any(|m| material_index(m).is_none()) {
         obj_materials.push(tobj::Material {
-            name: String::from("missing_material"),
+            name: String::from(MISSING_MATERIAL),
             ..Default::default()
         });
     }
 
     let mut materials = Vec::new();
     for m in obj_materials {
-        // let diffuse_texture = texture::Texture::from_rgba(&m.name, 255, 255, 255, 255, &device, &queue);
         let diffuse_texture = if let Some(texture) = &m.diffuse_texture {
-            load_texture(&texture, device, queue)?
+            match load_texture(texture, path, roots, textures, device, queue) {
+                Ok(texture) => texture,
+                Err(err) if policy == MissingAssetPolicy::Fallback => {
+                    log::warn!("{}, material {} of {path} will use {MISSING_TEXTURE}", describe(&err), m.name);
+                    missing_texture(roots, textures, device, queue)
+                },
+                Err(err) => return Err(err)
+            }
+        } else if m.name == MISSING_MATERIAL {
+            missing_texture(roots, textures, device, queue)
         } else {
             let color: Vec<u8> = m.diffuse
                 .unwrap_or([0.0, 0.0, 0.0])
                 .iter()
                 .map(|v| (v * 255.0).floor() as u8)
                 .collect();
-            texture::Texture::from_rgba(&m.name, color[0], color[1], color[2], 255, &device, &queue)
+            textures.add(texture::Texture::from_rgba(&m.name, color[0], color[1], color[2], 255, device, queue))
         };
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: bind_group_layout,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&diffuse_texture.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&diffuse_texture.sampler),
-                },
-            ],
-            label: None,
-        });
-
-        materials.push(model::Material {
-            name: m.name,
-            texture: diffuse_texture,
-            bind_group,
-        })
+        materials.push(model::Material::new(m.name, diffuse_texture, textures, bind_group_layout, device));
     }
 
     let meshes = models
@@ -103,26 +160,10 @@ pub fn load_model(filename: &str, bind_group_layout: &wgpu::BindGroupLayout, dev
                 })
                 .collect::<Vec<_>>();
 
-            let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("{:?} Vertex Buffer", filename)),
-                contents: bytemuck::cast_slice(&vertices),
-                usage: wgpu::BufferUsages::VERTEX,
-            });
-            let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("{:?} Index Buffer", filename)),
-                contents: bytemuck::cast_slice(&m.mesh.indices),
-                usage: wgpu::BufferUsages::INDEX,
-            });
-
-            model::Mesh {
-                name: String::from(filename),
-                vertex_buffer,
-                index_buffer,
-                num_elements: m.mesh.indices.len() as u32,
-                material: m.mesh.material_id.unwrap_or(0),
-            }
+            let material = material_index(&m).unwrap_or(missing_material);
+            model::Mesh::new(String::from(filename), &vertices, &m.mesh.indices, material, device)
         })
         .collect::<Vec<_>>();
 
-    Ok(model::Model { meshes, materials })
+    Ok(model::Model::new(meshes, materials))
 }
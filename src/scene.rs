@@ -0,0 +1,552 @@
+use crate::model::Model;
+use crate::error::{
+    Error,
+    Result
+};
+use crate::assets::{
+    AssetId,
+    Handle
+};
+use crate::instance::{
+    InstanceId,
+    InstanceRaw,
+    InstanceSet
+};
+
+use std::collections::HashMap;
+use cgmath::{
+    Matrix4,
+    Vector3,
+    Quaternion,
+    One,
+    SquareMatrix,
+    VectorSpace
+};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>
+}
+
+impl Transform {
+    pub fn new<V, Q, S>(translation: V, rotation: Q, scale: S) -> Self
+    where
+        V: Into<Vector3<f32>>,
+        Q: Into<Quaternion<f32>>,
+        S: Into<Vector3<f32>>
+    {
+        Self {
+            translation: translation.into(),
+            rotation: rotation.into(),
+            scale: scale.into()
+        }
+    }
+
+    pub fn from_translation<V: Into<Vector3<f32>>>(translation: V) -> Self {
+        Self {
+            translation: translation.into(),
+            ..Default::default()
+        }
+    }
+
+    // Blends translation and scale linearly and rotation along the shortest arc
+    pub fn lerp(&self, other: &Transform, amount: f32) -> Self {
+        Self {
+            translation: self.translation.lerp(other.translation, amount),
+            rotation: self.rotation.slerp(other.rotation, amount),
+            scale: self.scale.lerp(other.scale, amount)
+        }
+    }
+
+    pub fn to_matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation) *
+        Matrix4::from(self.rotation) *
+        Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Vector3::new(0.0, 0.0, 0.0),
+            rotation: Quaternion::one(),
+            scale: Vector3::new(1.0, 1.0, 1.0)
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    index: usize,
+    generation: u32
+}
+
+struct Node {
+    transform: Transform,
+    // The transform at the start of the current simulation step, for render interpolation
+    previous: Transform,
+    model: Option<(Handle<Model>, InstanceId)>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    world: Matrix4<f32>,
+    // The matrix its instance is drawn with: the world matrix, or an interpolated one while moving
+    drawn: Matrix4<f32>,
+    dirty: bool,
+    // Whether the instance holds an interpolated matrix rather than the world matrix
+    interpolated: bool
+}
+
+struct NodeSlot {
+    generation: u32,
+    node: Option<Node>
+}
+
+pub struct Scene {
+    nodes: Vec<NodeSlot>,
+    free_nodes: Vec<usize>,
+    roots: Vec<NodeId>,
+    instance_sets: HashMap<AssetId, InstanceSet>,
+    changed: bool,
+    interpolating: bool
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            free_nodes: Vec::new(),
+            roots: Vec::new(),
+            instance_sets: HashMap::new(),
+            changed: false,
+            interpolating: false
+        }
+    }
+
+    fn get(&self, id: NodeId) -> Option<&Node> {
+        self.nodes
+            .get(id.index)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.node.as_ref())
+    }
+
+    fn get_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.nodes
+            .get_mut(id.index)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.node.as_mut())
+    }
+
+    fn node(&self, id: NodeId) -> &Node {
+        self.get(id).unwrap_or_else(|| panic!("{id:?} does not refer to a live node"))
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        self.get_mut(id).unwrap_or_else(|| panic!("{id:?} does not refer to a live node"))
+    }
+
+    fn spawn_instance(&mut self, model: Option<Handle<Model>>) -> Option<(Handle<Model>, InstanceId)> {
+        let model = model?;
+        // The real world matrix is written by the next update_world_matrices
+        let instance = self.instance_sets
+            .entry(model.id())
+            .or_insert_with(InstanceSet::new)
+            .spawn(InstanceRaw::from_matrix(Matrix4::identity()));
+        Some((model, instance))
+    }
+
+    fn despawn_instance(&mut self, model: &Handle<Model>, instance: InstanceId) {
+        let Some(instance_set) = self.instance_sets.get_mut(&model.id()) else {
+            return;
+        };
+        instance_set.despawn(instance);
+        if instance_set.len() == 0 {
+            self.instance_sets.remove(&model.id());
+        }
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.get(id).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len() - self.free_nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn add(&mut self, parent: Option<NodeId>, transform: Transform, model: Option<Handle<Model>>) -> NodeId {
+        if let Some(parent) = parent {
+            self.node(parent);
+        }
+
+        let node = Node {
+            transform,
+            previous: transform,
+            model: self.spawn_instance(model),
+            parent,
+            children: Vec::new(),
+            world: Matrix4::identity(),
+            drawn: Matrix4::identity(),
+            dirty: true,
+            interpolated: false
+        };
+        let id = match self.free_nodes.pop() {
+            Some(index) => {
+                let slot = &mut self.nodes[index];
+                slot.node = Some(node);
+                NodeId { index, generation: slot.generation }
+            },
+            None => {
+                self.nodes.push(NodeSlot { generation: 0, node: Some(node) });
+                NodeId { index: self.nodes.len() - 1, generation: 0 }
+            }
+        };
+
+        match parent {
+            Some(parent) => self.node_mut(parent).children.push(id),
+            None => self.roots.push(id)
+        }
+        self.changed = true;
+        id
+    }
+
+    // Removes the node together with all of its descendants
+    pub fn despawn(&mut self, id: NodeId) {
+        let Some(parent) = self.get(id).map(|node| node.parent) else {
+            return;
+        };
+        match parent {
+            Some(parent) => self.node_mut(parent).children.retain(|&child| child != id),
+            None => self.roots.retain(|&root| root != id)
+        }
+
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            let slot = &mut self.nodes[id.index];
+            let Some(node) = slot.node.take() else {
+                continue;
+            };
+            slot.generation = slot.generation.wrapping_add(1);
+            self.free_nodes.push(id.index);
+
+            if let Some((model, instance)) = &node.model {
+                self.despawn_instance(model, *instance);
+            }
+            stack.extend(node.children);
+        }
+        self.changed = true;
+    }
+
+    pub fn transform(&self, id: NodeId) -> &Transform {
+        &self.node(id).transform
+    }
+
+    pub fn transform_mut(&mut self, id: NodeId) -> &mut Transform {
+        self.changed = true;
+        let node = self.node_mut(id);
+        node.dirty = true;
+        &mut node.transform
+    }
+
+    pub fn model(&self, id: NodeId) -> Option<&Handle<Model>> {
+        self.node(id).model.as_ref().map(|(model, _)| model)
+    }
+
+    pub fn set_model(&mut self, id: NodeId, model: Option<Handle<Model>>) {
+        if self.model(id) == model.as_ref() {
+            return;
+        }
+        if let Some((old_model, instance)) = self.node_mut(id).model.take() {
+            self.despawn_instance(&old_model, instance);
+        }
+        let instance = self.spawn_instance(model);
+
+        let node = self.node_mut(id);
+        node.model = instance;
+        node.dirty = true;
+        self.changed = true;
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id).parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.node(id).children
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> Result<()> {
+        if let Some(parent) = parent.filter(|&parent| !self.contains(parent)) {
+            return Err(Error::DespawnedNode(parent));
+        }
+
+        let mut ancestor = parent;
+        while let Some(ancestor_id) = ancestor {
+            if ancestor_id == id {
+                return Err(Error::InvalidParent { node: id, parent: parent.unwrap_or(id) });
+            }
+            ancestor = self.node(ancestor_id).parent;
+        }
+
+        match self.node(id).parent {
+            Some(old_parent) => self.node_mut(old_parent).children.retain(|&child| child != id),
+            None => self.roots.retain(|&root| root != id)
+        }
+        match parent {
+            Some(parent) => self.node_mut(parent).children.push(id),
+            None => self.roots.push(id)
+        }
+
+        let node = self.node_mut(id);
+        node.parent = parent;
+        node.dirty = true;
+        self.changed = true;
+        Ok(())
+    }
+
+    fn model_nodes_with(&self, matrix: fn(&Node) -> Matrix4<f32>) -> impl Iterator<Item = (NodeId, &Handle<Model>, Matrix4<f32>)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, slot)| {
+                let node = slot.node.as_ref()?;
+                let (model, _) = node.model.as_ref()?;
+                Some((NodeId { index, generation: slot.generation }, model, matrix(node)))
+            })
+    }
+
+    // Every node that has a model, with its world matrix as of the last update_world_matrices
+    pub fn model_nodes(&self) -> impl Iterator<Item = (NodeId, &Handle<Model>, Matrix4<f32>)> {
+        self.model_nodes_with(|node| node.world)
+    }
+
+    // Every node that has a model, with the matrix it is drawn with as of the last interpolate
+    pub(crate) fn drawn_model_nodes(&self) -> impl Iterator<Item = (NodeId, &Handle<Model>, Matrix4<f32>)> {
+        self.model_nodes_with(|node| node.drawn)
+    }
+
+    // Only valid after update_world_matrices has run for the latest changes
+    pub fn world_matrix(&self, id: NodeId) -> Matrix4<f32> {
+        self.node(id).world
+    }
+
+    pub fn update_world_matrices(&mut self) -> bool {
+        if !self.changed {
+            return false;
+        }
+
+        let mut stack: Vec<(NodeId, Matrix4<f32>, bool)> = self.roots
+            .iter()
+            .rev()
+            .map(|&root| (root, Matrix4::identity(), false))
+            .collect();
+        while let Some((id, parent_world, parent_dirty)) = stack.pop() {
+            let Some(node) = self.nodes[id.index].node.as_mut() else {
+                continue;
+            };
+            let dirty = node.dirty || parent_dirty;
+            if dirty {
+                node.world = parent_world * node.transform.to_matrix();
+                node.drawn = node.world;
+                node.dirty = false;
+                if let Some((model, instance)) = &node.model {
+                    if let Some(instance_set) = self.instance_sets.get_mut(&model.id()) {
+                        instance_set.set(*instance, InstanceRaw::from_matrix(node.world));
+                    }
+                }
+            }
+            stack.extend(node.children.iter().rev().map(|&child| (child, node.world, dirty)));
+        }
+
+        self.changed = false;
+        true
+    }
+
+    // Called before every fixed simulation step
+    pub(crate) fn begin_step(&mut self) {
+        for node in self.nodes.iter_mut().filter_map(|slot| slot.node.as_mut()) {
+            node.previous = node.transform;
+        }
+        self.interpolating = true;
+    }
+
+    // Writes matrices between each moving node's previous and current transform into its instance,
+    // and restores the exact world matrix once a node comes to rest
+    pub(crate) fn interpolate(&mut self, alpha: f32) {
+        if !self.interpolating {
+            return;
+        }
+
+        let mut any_moving = false;
+        let mut stack: Vec<(NodeId, Matrix4<f32>, bool)> = self.roots
+            .iter()
+            .rev()
+            .map(|&root| (root, Matrix4::identity(), false))
+            .collect();
+        while let Some((id, parent_matrix, parent_moving)) = stack.pop() {
+            let Some(node) = self.nodes[id.index].node.as_mut() else {
+                continue;
+            };
+            let moving = parent_moving || node.previous != node.transform;
+            let matrix = if moving {
+                parent_matrix * node.previous.lerp(&node.transform, alpha).to_matrix()
+            } else {
+                node.world
+            };
+            if moving || node.interpolated {
+                node.drawn = matrix;
+                if let Some((model, instance)) = &node.model {
+                    if let Some(instance_set) = self.instance_sets.get_mut(&model.id()) {
+                        instance_set.set(*instance, InstanceRaw::from_matrix(matrix));
+                    }
+                }
+            }
+            node.interpolated = moving;
+            any_moving |= moving;
+            stack.extend(node.children.iter().rev().map(|&child| (child, matrix, moving)));
+        }
+
+        self.interpolating = any_moving;
+    }
+
+    pub(crate) fn instance_sets_mut(&mut self) -> impl Iterator<Item = (AssetId, &mut InstanceSet)> {
+        self.instance_sets
+            .iter_mut()
+            .map(|(&model, instance_set)| (model, instance_set))
+    }
+
+    pub(crate) fn has_instances(&self, model: AssetId) -> bool {
+        self.instance_sets.contains_key(&model)
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets::Assets;
+
+    // An empty model is enough to hand out handles, as nothing here touches the GPU
+    fn model(assets: &mut Assets<Model>) -> Handle<Model> {
+        assets.add(Model::new(Vec::new(), Vec::new()))
+    }
+
+    fn translation(scene: &Scene, id: NodeId) -> Vector3<f32> {
+        scene.world_matrix(id).w.truncate()
+    }
+
+    #[test]
+    fn despawning_a_node_removes_its_subtree() {
+        let mut assets = Assets::new();
+        let model = model(&mut assets);
+        let mut scene = Scene::new();
+        let root = scene.add(None, Transform::default(), None);
+        let child = scene.add(Some(root), Transform::default(), Some(model.clone()));
+        let grandchild = scene.add(Some(child), Transform::default(), Some(model.clone()));
+        let sibling = scene.add(Some(root), Transform::default(), None);
+
+        scene.despawn(child);
+        assert!(!scene.contains(child));
+        assert!(!scene.contains(grandchild));
+        assert_eq!(scene.children(root), [sibling]);
+        assert_eq!(scene.len(), 2);
+        // Both instances went with their nodes
+        assert!(!scene.has_instances(model.id()));
+        assert_eq!(scene.model_nodes().count(), 0);
+    }
+
+    #[test]
+    fn stale_ids_are_rejected_after_their_slot_is_reused() {
+        let mut scene = Scene::new();
+        let old = scene.add(None, Transform::default(), None);
+        scene.despawn(old);
+        let new = scene.add(None, Transform::from_translation([1.0, 0.0, 0.0]), None);
+        assert_ne!(old, new);
+        assert!(!scene.contains(old));
+        assert!(scene.contains(new));
+
+        // Neither touches the node now living in the slot
+        scene.despawn(old);
+        assert!(scene.contains(new));
+        assert!(matches!(scene.set_parent(new, Some(old)), Err(Error::DespawnedNode(id)) if id == old));
+        assert_eq!(scene.parent(new), None);
+        assert_eq!(scene.roots(), [new]);
+    }
+
+    #[test]
+    fn set_parent_rejects_cycles() {
+        let mut scene = Scene::new();
+        let a = scene.add(None, Transform::default(), None);
+        let b = scene.add(Some(a), Transform::default(), None);
+        let c = scene.add(Some(b), Transform::default(), None);
+
+        assert!(matches!(scene.set_parent(a, Some(c)), Err(Error::InvalidParent { .. })));
+        assert!(matches!(scene.set_parent(a, Some(a)), Err(Error::InvalidParent { .. })));
+        assert_eq!(scene.parent(a), None);
+        assert_eq!(scene.children(c), []);
+
+        scene.set_parent(c, Some(a)).unwrap();
+        assert_eq!(scene.children(a), [b, c]);
+        assert_eq!(scene.children(b), []);
+        scene.set_parent(b, None).unwrap();
+        assert_eq!(scene.roots(), [a, b]);
+    }
+
+    #[test]
+    fn moving_nodes_are_drawn_between_steps() {
+        let mut assets = Assets::new();
+        let model = model(&mut assets);
+        let mut scene = Scene::new();
+        let parent = scene.add(None, Transform::default(), None);
+        let child = scene.add(Some(parent), Transform::from_translation([0.0, 1.0, 0.0]), Some(model.clone()));
+        let still = scene.add(None, Transform::from_translation([0.0, 0.0, 7.0]), Some(model));
+        scene.update_world_matrices();
+
+        scene.begin_step();
+        scene.transform_mut(parent).translation = Vector3::new(8.0, 0.0, 0.0);
+        scene.update_world_matrices();
+        scene.interpolate(0.25);
+
+        let drawn: HashMap<NodeId, Matrix4<f32>> = scene.drawn_model_nodes().map(|(id, _, matrix)| (id, matrix)).collect();
+        assert_eq!(drawn[&child].w.truncate(), Vector3::new(2.0, 1.0, 0.0));
+        assert_eq!(drawn[&still].w.truncate(), Vector3::new(0.0, 0.0, 7.0));
+        assert_eq!(translation(&scene, child), Vector3::new(8.0, 1.0, 0.0));
+
+        // A step without movement settles everything on its world matrix
+        scene.begin_step();
+        scene.interpolate(0.5);
+        assert!(scene.drawn_model_nodes().all(|(id, _, matrix)| matrix == scene.world_matrix(id)));
+        assert!(!scene.interpolating);
+    }
+
+    #[test]
+    fn moving_a_parent_updates_its_descendants() {
+        let mut scene = Scene::new();
+        let parent = scene.add(None, Transform::from_translation([1.0, 0.0, 0.0]), None);
+        let child = scene.add(Some(parent), Transform::from_translation([0.0, 2.0, 0.0]), None);
+        let grandchild = scene.add(Some(child), Transform::from_translation([0.0, 0.0, 3.0]), None);
+        assert!(scene.update_world_matrices());
+        assert_eq!(translation(&scene, grandchild), Vector3::new(1.0, 2.0, 3.0));
+
+        scene.transform_mut(parent).translation = Vector3::new(5.0, 0.0, 0.0);
+        assert!(scene.update_world_matrices());
+        assert_eq!(translation(&scene, child), Vector3::new(5.0, 2.0, 0.0));
+        assert_eq!(translation(&scene, grandchild), Vector3::new(5.0, 2.0, 3.0));
+
+        // Nothing changed since
+        assert!(!scene.update_world_matrices());
+    }
+}
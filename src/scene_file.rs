@@ -0,0 +1,230 @@
+use crate::camera::{
+    Camera,
+    ProjectionMode
+};
+use crate::light::Light;
+use crate::renderer::RenderSettings;
+use crate::scene::Transform;
+use crate::error::{
+    Error,
+    Result
+};
+
+use cgmath::{
+    Deg,
+    Euler,
+    Quaternion
+};
+use serde::{
+    Deserialize,
+    Serialize
+};
+use std::{
+    fs,
+    path::Path
+};
+
+// Rotations are Euler angles in degrees so scene files stay editable by hand
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransformDesc {
+    pub translation: [f32; 3],
+    // Turns about the model's own x axis, then its new y axis, then its new z axis (the same as
+    // turning about the parent's z, then y, then x)
+    pub rotation: [f32; 3],
+    pub scale: [f32; 3]
+}
+
+impl Default for TransformDesc {
+    fn default() -> Self {
+        Self {
+            translation: [0.0, 0.0, 0.0],
+            rotation: [0.0, 0.0, 0.0],
+            scale: [1.0, 1.0, 1.0]
+        }
+    }
+}
+
+impl From<TransformDesc> for Transform {
+    fn from(desc: TransformDesc) -> Self {
+        let [x, y, z] = desc.rotation;
+        Transform::new(desc.translation, Quaternion::from(Euler::new(Deg(x), Deg(y), Deg(z))), desc.scale)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ModelDesc {
+    // Resolved through the asset roots like any other model path
+    pub path: String,
+    #[serde(default)]
+    pub instances: Vec<TransformDesc>
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LightDesc {
+    pub position: [f32; 3],
+    pub color: [f32; 3]
+}
+
+impl From<LightDesc> for Light {
+    fn from(desc: LightDesc) -> Self {
+        Light::new(desc.position, desc.color)
+    }
+}
+
+// Yaw, pitch, roll and the vertical field of view are in degrees
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraDesc {
+    pub position: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub roll: f32,
+    pub fov: f32,
+    pub projection: ProjectionMode
+}
+
+impl Default for CameraDesc {
+    fn default() -> Self {
+        Self {
+            position: [0.0, 0.0, -5.0],
+            yaw: 90.0,
+            pitch: 0.0,
+            roll: 0.0,
+            fov: Camera::DEFAULT_FOVY.0,
+            projection: ProjectionMode::default()
+        }
+    }
+}
+
+impl From<CameraDesc> for Camera {
+    fn from(desc: CameraDesc) -> Self {
+        Camera::new(desc.position, Deg(desc.yaw), Deg(desc.pitch))
+            .with_roll(Deg(desc.roll))
+            .with_fovy(Deg(desc.fov))
+    }
+}
+
+// Everything needed to set up a scene without recompiling, stored as RON
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneFile {
+    pub models: Vec<ModelDesc>,
+    pub lights: Vec<LightDesc>,
+    pub camera: CameraDesc,
+    pub render: RenderSettings
+}
+
+impl SceneFile {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|source| Error::Io { path: path.to_path_buf(), source })?;
+        ron::from_str(&text).map_err(|source| Error::SceneParse { path: path.to_path_buf(), source })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        fs::write(path, text).map_err(|source| Error::Io { path: path.to_path_buf(), source })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cgmath::{
+        InnerSpace,
+        Rotation,
+        Rotation3,
+        Vector3
+    };
+    use std::{
+        env,
+        process
+    };
+
+    fn scene() -> SceneFile {
+        SceneFile {
+            models: vec![
+                ModelDesc {
+                    path: String::from("teapot.obj"),
+                    instances: vec![
+                        TransformDesc::default(),
+                        TransformDesc {
+                            translation: [3.0, -0.25, 1.5],
+                            rotation: [10.0, 45.0, -30.0],
+                            scale: [0.5, 2.0, 0.125]
+                        }
+                    ]
+                },
+                ModelDesc {
+                    path: String::from("models/cube.obj"),
+                    instances: Vec::new()
+                }
+            ],
+            lights: vec![LightDesc {
+                position: [-3.0, 4.0, -2.0],
+                color: [0.3, 0.3, 0.6]
+            }],
+            camera: CameraDesc {
+                position: [0.1, 1.0, -8.0],
+                yaw: 80.0,
+                pitch: -5.5,
+                roll: 12.0,
+                fov: 60.0,
+                projection: ProjectionMode::Orthographic
+            },
+            render: RenderSettings {
+                clear_color: [0.1, 0.1, 0.12, 1.0],
+                wireframe: true,
+                msaa_samples: 4
+            }
+        }
+    }
+
+    #[test]
+    fn scenes_round_trip_through_ron() {
+        let scene = scene();
+        let text = ron::ser::to_string_pretty(&scene, ron::ser::PrettyConfig::default()).unwrap();
+        assert_eq!(ron::from_str::<SceneFile>(&text).unwrap(), scene);
+    }
+
+    #[test]
+    fn scenes_round_trip_through_save_and_load() {
+        let path = env::temp_dir().join(format!("scene_file_round_trip_{}.ron", process::id()));
+        let scene = scene();
+        scene.save(&path).unwrap();
+        let loaded = SceneFile::load(&path);
+        let _ = fs::remove_file(&path);
+        assert_eq!(loaded.unwrap(), scene);
+    }
+
+    #[test]
+    fn left_out_fields_keep_their_defaults() {
+        let scene: SceneFile = ron::from_str("(models: [(path: \"a.obj\", instances: [(scale: (2.0, 2.0, 2.0))])])").unwrap();
+        assert_eq!(scene.models[0].instances[0], TransformDesc { scale: [2.0, 2.0, 2.0], ..Default::default() });
+        assert_eq!(scene.camera, CameraDesc::default());
+        assert_eq!(scene.render, RenderSettings::default());
+    }
+
+    #[test]
+    fn rotation_turns_about_x_then_y_then_z() {
+        let desc = TransformDesc {
+            rotation: [30.0, 45.0, 60.0],
+            ..Default::default()
+        };
+        let rotation = Transform::from(desc).rotation;
+        let expected = Quaternion::from_angle_x(Deg(30.0)) * Quaternion::from_angle_y(Deg(45.0)) * Quaternion::from_angle_z(Deg(60.0));
+        for axis in [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()] {
+            assert!((rotation.rotate_vector(axis) - expected.rotate_vector(axis)).magnitude() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn the_example_scene_parses() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes/review.ron");
+        let scene = SceneFile::load(path).unwrap();
+        assert_eq!(scene.models.len(), 2);
+    }
+}
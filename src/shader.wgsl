@@ -31,11 +31,18 @@ struct Light {
     color: vec3<f32>
 }
 
+const MAX_LIGHTS: u32 = 8u;
+
+struct Lights {
+    lights: array<Light, MAX_LIGHTS>,
+    count: u32
+}
+
 @group(1) @binding(0)
 var<uniform> camera: Camera;
 
 @group(2) @binding(0)
-var<uniform> light: Light;
+var<uniform> lights: Lights;
 
 @vertex
 fn vs_main(vertex: VertexInput, instance: InstanceInput) -> VertexOutput {
@@ -70,25 +77,32 @@ var texture_sampler: sampler;
 fn fs_main(vertex: VertexOutput) -> @location(0) vec4<f32> {
     let object_color = textureSample(texture, texture_sampler, vertex.texture_coords);
 
+    let view_dir = normalize(camera.position.xyz - vertex.world_position);
     let ambient_strength = 0.1;
-    let ambient_color = light.color * ambient_strength;
-    
-    let light_dir = normalize(light.position - vertex.world_position);
-    
-    let diffuse_strength = max(dot(vertex.world_normal, light_dir), 0.0);
-    let diffuse_color = light.color * diffuse_strength;
 
-    let view_dir = normalize(camera.position.xyz - vertex.world_position);
-    let reflect_dir = reflect(-light_dir, vertex.world_normal); // Phong
-    // let half_dir = normalize(view_dir + light_dir); // Blinn-Phong
+    var lighting = vec3<f32>(0.0, 0.0, 0.0);
+    for (var i = 0u; i < min(lights.count, MAX_LIGHTS); i++) {
+        let light = lights.lights[i];
+
+        let ambient_color = light.color * ambient_strength;
+
+        let light_dir = normalize(light.position - vertex.world_position);
+
+        let diffuse_strength = max(dot(vertex.world_normal, light_dir), 0.0);
+        let diffuse_color = light.color * diffuse_strength;
+
+        let reflect_dir = reflect(-light_dir, vertex.world_normal); // Phong
+        // let half_dir = normalize(view_dir + light_dir); // Blinn-Phong
+
+        // let specular_strength = pow(max(dot(view_dir, vertex.world_normal), 0.0), 32.0);
+        let specular_strength = pow(max(dot(view_dir, reflect_dir), 0.0), 32.0); // Phong
+        // let specular_strength = pow(max(dot(vertex.world_normal, half_dir), 0.0), 32.0); // Blinn-Phong
+        let specular_color = specular_strength * light.color;
 
-    // let specular_strength = pow(max(dot(view_dir, vertex.world_normal), 0.0), 32.0);
-    let specular_strength = pow(max(dot(view_dir, reflect_dir), 0.0), 32.0); // Phong
-    // let specular_strength = pow(max(dot(vertex.world_normal, half_dir), 0.0), 32.0); // Blinn-Phong
-    let specular_color = specular_strength * light.color;
+        lighting += ambient_color + diffuse_color + specular_color;
+    }
 
-    let result = (ambient_color + diffuse_color + specular_color) * object_color.xyz;
+    let result = lighting * object_color.xyz;
 
     return vec4<f32>(result, object_color.a);
-    // return vec4<f32>(specular_color, object_color.a);
 }
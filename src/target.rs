@@ -0,0 +1,211 @@
+use std::path::Path;
+use std::sync::mpsc;
+
+use crate::error::{
+    Error,
+    Result
+};
+
+pub struct WindowTarget {
+    pub window: sdl2::video::Window,
+    pub surface: wgpu::Surface,
+    pub surface_config: wgpu::SurfaceConfiguration
+}
+
+impl WindowTarget {
+    // Falls back to the closest supported mode, ending at Fifo which every surface supports
+    pub fn select_present_mode(requested: wgpu::PresentMode, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+        use wgpu::PresentMode::*;
+        let candidates: &[wgpu::PresentMode] = match requested {
+            // Resolved by wgpu itself
+            AutoVsync | AutoNoVsync => return requested,
+            Mailbox => &[Mailbox, Immediate],
+            Immediate => &[Immediate, Mailbox],
+            FifoRelaxed => &[FifoRelaxed],
+            Fifo => &[]
+        };
+        let present_mode = candidates
+            .iter()
+            .copied()
+            .find(|mode| supported.contains(mode))
+            .unwrap_or(Fifo);
+        if present_mode != requested {
+            log::warn!("Present mode {requested:?} is not supported, using {present_mode:?}");
+        }
+        present_mode
+    }
+
+    pub fn set_present_mode(&mut self, present_mode: wgpu::PresentMode, adapter: &wgpu::Adapter, device: &wgpu::Device) -> wgpu::PresentMode {
+        let surface_caps = self.surface.get_capabilities(adapter);
+        self.surface_config.present_mode = Self::select_present_mode(present_mode, &surface_caps.present_modes);
+        self.surface.configure(device, &self.surface_config);
+        self.surface_config.present_mode
+    }
+}
+
+pub struct OffscreenTarget {
+    texture: wgpu::Texture,
+    readback_buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32
+}
+
+impl OffscreenTarget {
+    pub const TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    pub fn new(width: u32, height: u32, device: &wgpu::Device) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("offscreen_texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: Self::TEXTURE_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[]
+        });
+
+        // Rows copied out of a texture must be aligned to 256 bytes
+        let unpadded_bytes_per_row = 4 * width;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(align) * align;
+
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("offscreen_readback_buffer"),
+            size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false
+        });
+
+        Self {
+            texture,
+            readback_buffer,
+            width,
+            height,
+            padded_bytes_per_row
+        }
+    }
+
+    pub fn copy_to_readback(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &self.readback_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.padded_bytes_per_row),
+                    rows_per_image: Some(self.height)
+                }
+            },
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1
+            }
+        );
+    }
+
+    pub fn read_image(&self, device: &wgpu::Device) -> Result<image::RgbaImage> {
+        let buffer_slice = self.readback_buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::Maintain::Wait);
+        receiver.recv().map_err(|_| wgpu::BufferAsyncError)??;
+
+        let unpadded_bytes_per_row = (4 * self.width) as usize;
+        let mut pixels = Vec::with_capacity(unpadded_bytes_per_row * self.height as usize);
+        {
+            let data = buffer_slice.get_mapped_range();
+            for row in data.chunks(self.padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..unpadded_bytes_per_row]);
+            }
+        }
+        self.readback_buffer.unmap();
+
+        image::RgbaImage::from_raw(self.width, self.height, pixels)
+            .ok_or(Error::Readback(wgpu::BufferAsyncError))
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P, device: &wgpu::Device) -> Result<()> {
+        let path = path.as_ref();
+        self.read_image(device)?
+            .save_with_format(path, image::ImageFormat::Png)
+            .map_err(|source| Error::Encode { path: path.to_path_buf(), source })
+    }
+}
+
+pub enum RenderTarget {
+    Window(WindowTarget),
+    Offscreen(OffscreenTarget)
+}
+
+pub struct TargetFrame {
+    pub view: wgpu::TextureView,
+    surface_texture: Option<wgpu::SurfaceTexture>
+}
+
+impl TargetFrame {
+    pub fn present(self) {
+        if let Some(surface_texture) = self.surface_texture {
+            surface_texture.present();
+        }
+    }
+}
+
+impl RenderTarget {
+    pub fn size(&self) -> (u32, u32) {
+        match self {
+            Self::Window(target) => (target.surface_config.width, target.surface_config.height),
+            Self::Offscreen(target) => (target.width, target.height)
+        }
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        match self {
+            Self::Window(target) => target.surface_config.format,
+            Self::Offscreen(_) => OffscreenTarget::TEXTURE_FORMAT
+        }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32, device: &wgpu::Device) {
+        match self {
+            Self::Window(target) => {
+                (target.surface_config.width, target.surface_config.height) = (width, height);
+                target.surface.configure(device, &target.surface_config);
+            },
+            Self::Offscreen(target) => { *target = OffscreenTarget::new(width, height, device) }
+        }
+    }
+
+    pub fn acquire_frame(&self) -> Result<TargetFrame, wgpu::SurfaceError> {
+        match self {
+            Self::Window(target) => {
+                let surface_texture = target.surface.get_current_texture()?;
+                let view = surface_texture.texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("surface_texture_view"),
+                    ..Default::default()
+                });
+                Ok(TargetFrame { view, surface_texture: Some(surface_texture) })
+            },
+            Self::Offscreen(target) => {
+                let view = target.texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("offscreen_texture_view"),
+                    ..Default::default()
+                });
+                Ok(TargetFrame { view, surface_texture: None })
+            }
+        }
+    }
+}
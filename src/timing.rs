@@ -0,0 +1,230 @@
+use std::{
+    thread,
+    time::{
+        Duration,
+        Instant
+    }
+};
+
+// Sleeps away whatever is left of each frame's budget, so render time counts towards it
+#[derive(Clone, Debug)]
+pub struct FrameLimiter {
+    frame_time: Option<Duration>,
+    next_frame: Option<Instant>
+}
+
+impl FrameLimiter {
+    pub fn new(target_fps: Option<u32>) -> Self {
+        Self {
+            frame_time: target_fps
+                .filter(|&fps| fps > 0)
+                .map(|fps| Duration::from_secs(1) / fps),
+            next_frame: None
+        }
+    }
+
+    pub fn target_fps(&self) -> Option<u32> {
+        self.frame_time.map(|frame_time| (1.0 / frame_time.as_secs_f64()).round() as u32)
+    }
+
+    pub fn set_target_fps(&mut self, target_fps: Option<u32>) {
+        *self = Self::new(target_fps);
+    }
+
+    pub fn wait(&mut self) {
+        let Some(frame_time) = self.frame_time else {
+            return;
+        };
+
+        // Frames that ran over start a fresh schedule instead of rushing to catch up
+        let now = Instant::now();
+        let frame_start = match self.next_frame {
+            Some(next_frame) if next_frame > now => {
+                thread::sleep(next_frame - now);
+                next_frame
+            },
+            _ => now
+        };
+        self.next_frame = Some(frame_start + frame_time);
+    }
+}
+
+// Turns variable frame times into a whole number of fixed simulation steps
+#[derive(Clone, Debug)]
+pub struct FixedTimestep {
+    step: Duration,
+    accumulator: Duration,
+    time_scale: f64,
+    paused: bool,
+    pending_steps: u32
+}
+
+impl FixedTimestep {
+    // Scaled frame times are clamped to this, so a long stall or a high time scale does not trigger
+    // a burst of catch-up steps
+    pub(crate) const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+    pub const MIN_TIME_SCALE: f64 = 1.0 / 64.0;
+    pub const MAX_TIME_SCALE: f64 = 64.0;
+
+    // Panics if step is zero, which would never stop stepping
+    pub fn new(step: Duration) -> Self {
+        assert!(!step.is_zero(), "the fixed timestep must be longer than zero");
+        Self {
+            step,
+            accumulator: Duration::ZERO,
+            time_scale: 1.0,
+            paused: false,
+            pending_steps: 0
+        }
+    }
+
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    // Clamped to MIN_TIME_SCALE..=MAX_TIME_SCALE, and ignored if not finite; pause to stop the
+    // simulation altogether
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        if !time_scale.is_finite() {
+            return;
+        }
+        self.time_scale = time_scale.clamp(Self::MIN_TIME_SCALE, Self::MAX_TIME_SCALE);
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    // Runs exactly one step on the next frame, for stepping through a paused simulation
+    pub fn single_step(&mut self) {
+        self.pending_steps += 1;
+    }
+
+    // Returns how many steps to simulate for a frame that took frame_time
+    pub fn advance(&mut self, frame_time: Duration) -> u32 {
+        if self.paused {
+            return std::mem::take(&mut self.pending_steps);
+        }
+
+        // Capped before scaling too, so an enormous frame time cannot overflow
+        self.accumulator += frame_time.min(Self::MAX_FRAME_TIME).mul_f64(self.time_scale).min(Self::MAX_FRAME_TIME);
+        let mut steps = std::mem::take(&mut self.pending_steps);
+        while self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        steps
+    }
+
+    // How far the simulation has progressed towards its next step, from 0 to 1
+    pub fn alpha(&self) -> f32 {
+        if self.paused {
+            1.0
+        } else {
+            (self.accumulator.as_secs_f64() / self.step.as_secs_f64()) as f32
+        }
+    }
+}
+
+impl Default for FixedTimestep {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1) / 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn leftover_time_carries_over_to_the_next_frame() {
+        let mut timestep = FixedTimestep::new(ms(10));
+        assert_eq!(timestep.advance(ms(25)), 2);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-6);
+        assert_eq!(timestep.advance(ms(5)), 1);
+        assert_eq!(timestep.alpha(), 0.0);
+        assert_eq!(timestep.advance(ms(9)), 0);
+        assert!((timestep.alpha() - 0.9).abs() < 1e-6);
+    }
+
+    #[test]
+    fn long_frames_are_clamped() {
+        let mut timestep = FixedTimestep::new(ms(10));
+        assert_eq!(timestep.advance(Duration::from_secs(10)), 25);
+    }
+
+    #[test]
+    fn time_scale_cannot_get_around_the_frame_time_clamp() {
+        let mut timestep = FixedTimestep::new(ms(10));
+        timestep.set_time_scale(f64::MAX);
+        assert_eq!(timestep.time_scale(), FixedTimestep::MAX_TIME_SCALE);
+        assert_eq!(timestep.advance(ms(100)), 25);
+        assert_eq!(timestep.advance(Duration::MAX), 25);
+
+        timestep.set_time_scale(0.0);
+        assert_eq!(timestep.time_scale(), FixedTimestep::MIN_TIME_SCALE);
+    }
+
+    #[test]
+    fn non_finite_time_scales_are_ignored() {
+        let mut timestep = FixedTimestep::new(ms(10));
+        timestep.set_time_scale(2.0);
+        for time_scale in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            timestep.set_time_scale(time_scale);
+            assert_eq!(timestep.time_scale(), 2.0);
+        }
+        assert_eq!(timestep.advance(ms(10)), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "longer than zero")]
+    fn zero_steps_are_rejected() {
+        FixedTimestep::new(Duration::ZERO);
+    }
+
+    #[test]
+    fn time_scale_changes_how_many_steps_run() {
+        let mut timestep = FixedTimestep::new(ms(10));
+        timestep.set_time_scale(0.5);
+        assert_eq!(timestep.advance(ms(40)), 2);
+        timestep.set_time_scale(2.0);
+        assert_eq!(timestep.advance(ms(40)), 8);
+    }
+
+    #[test]
+    fn paused_timesteps_only_run_single_steps() {
+        let mut timestep = FixedTimestep::new(ms(10));
+        timestep.advance(ms(5));
+        timestep.set_paused(true);
+        assert_eq!(timestep.advance(ms(100)), 0);
+        assert_eq!(timestep.alpha(), 1.0);
+
+        timestep.single_step();
+        timestep.single_step();
+        assert_eq!(timestep.advance(ms(100)), 2);
+        assert_eq!(timestep.advance(ms(100)), 0);
+
+        // The time from before the pause is still there
+        timestep.set_paused(false);
+        assert_eq!(timestep.advance(ms(5)), 1);
+    }
+
+    #[test]
+    fn frame_limiter_round_trips_the_target_fps() {
+        assert_eq!(FrameLimiter::new(Some(60)).target_fps(), Some(60));
+        assert_eq!(FrameLimiter::new(Some(0)).target_fps(), None);
+        assert_eq!(FrameLimiter::new(None).target_fps(), None);
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    borrow::Cow,
+    env,
+    fmt,
+    fs,
+    io,
+    path::{
+        Component,
+        Path,
+        PathBuf
+    }
+};
+
+use crate::error::{
+    Error,
+    Result
+};
+
+#[cfg(feature = "embed-assets")]
+mod embedded {
+    include!(concat!(env!("OUT_DIR"), "/embedded_assets.rs"));
+}
+
+#[cfg(feature = "embed-assets")]
+fn embedded_asset(name: &str) -> Option<(&'static str, &'static [u8])> {
+    embedded::EMBEDDED_ASSETS
+        .iter()
+        .find(|(embedded_name, _)| *embedded_name == name)
+        .copied()
+}
+
+#[cfg(not(feature = "embed-assets"))]
+fn embedded_asset(_name: &str) -> Option<(&'static str, &'static [u8])> {
+    None
+}
+
+// Embedded assets are keyed by their path inside res/ with forward slashes
+fn embedded_name(path: &Path) -> Option<String> {
+    let mut parts: Vec<&str> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_str()?),
+            Component::ParentDir => { parts.pop()?; },
+            Component::CurDir => {},
+            Component::RootDir | Component::Prefix(_) => return None
+        }
+    }
+    Some(parts.join("/"))
+}
+
+// A resolved asset location, either a canonical file on disk or a file compiled into the binary
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AssetPath {
+    File(PathBuf),
+    Embedded(&'static str)
+}
+
+impl AssetPath {
+    pub fn read(&self) -> io::Result<Cow<'static, [u8]>> {
+        match self {
+            Self::File(path) => fs::read(path).map(Cow::Owned),
+            Self::Embedded(name) => embedded_asset(name)
+                .map(|(_, data)| Cow::Borrowed(data))
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{name} is not embedded")))
+        }
+    }
+
+    pub fn read_to_string(&self) -> io::Result<String> {
+        String::from_utf8(self.read()?.into_owned())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    // Embedded assets keep the embedded: prefix they are displayed with
+    pub fn to_path_buf(&self) -> PathBuf {
+        match self {
+            Self::File(path) => path.clone(),
+            Self::Embedded(name) => PathBuf::from(format!("embedded:{name}"))
+        }
+    }
+
+    pub fn file_name(&self) -> Option<&str> {
+        match self {
+            Self::File(path) => path.file_name().and_then(|name| name.to_str()),
+            Self::Embedded(name) => name.rsplit('/').next()
+        }
+    }
+
+    fn sibling(&self, filename: &Path) -> Option<AssetPath> {
+        match self {
+            Self::File(path) => find_file(&path.parent()?.join(filename)),
+            Self::Embedded(name) => find_embedded(&Path::new(name).parent()?.join(filename))
+        }
+    }
+}
+
+impl fmt::Display for AssetPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "{}", path.display()),
+            Self::Embedded(name) => write!(f, "embedded:{name}")
+        }
+    }
+}
+
+fn find_file(path: &Path) -> Option<AssetPath> {
+    // Canonical paths make every spelling of the same file share one cache entry
+    path.canonicalize().ok().filter(|path| path.is_file()).map(AssetPath::File)
+}
+
+fn find_embedded(path: &Path) -> Option<AssetPath> {
+    embedded_asset(&embedded_name(path)?).map(|(name, _)| AssetPath::Embedded(name))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AssetRoot {
+    Dir(PathBuf),
+    Embedded
+}
+
+// Roots searched in order for relative asset paths
+#[derive(Clone, Debug)]
+pub struct AssetRoots {
+    roots: Vec<AssetRoot>
+}
+
+impl AssetRoots {
+    pub const ENV_VAR: &'static str = "WINDOW_ASSET_PATH";
+
+    pub fn new(roots: Vec<AssetRoot>) -> Self {
+        Self { roots }
+    }
+
+    // Explicit roots first, then $WINDOW_ASSET_PATH, then res/ next to the executable,
+    // then the assets embedded into the binary, then the build.rs copy
+    pub fn with_defaults(explicit_roots: Vec<PathBuf>) -> Self {
+        let mut roots: Vec<AssetRoot> = explicit_roots.into_iter().map(AssetRoot::Dir).collect();
+        if let Some(env_roots) = env::var_os(Self::ENV_VAR) {
+            roots.extend(env::split_paths(&env_roots).map(AssetRoot::Dir));
+        }
+        if let Some(exe_dir) = env::current_exe().ok().as_deref().and_then(Path::parent) {
+            roots.push(AssetRoot::Dir(exe_dir.join("res")));
+        }
+        if cfg!(feature = "embed-assets") {
+            roots.push(AssetRoot::Embedded);
+        }
+        if let Some(out_dir) = option_env!("OUT_DIR") {
+            roots.push(AssetRoot::Dir(Path::new(out_dir).join("res")));
+        }
+        Self { roots }
+    }
+
+    pub fn roots(&self) -> &[AssetRoot] {
+        &self.roots
+    }
+
+    pub fn push(&mut self, root: AssetRoot) {
+        self.roots.push(root);
+    }
+
+    pub fn resolve<P: AsRef<Path>>(&self, filename: P) -> Result<AssetPath> {
+        self.resolve_near(filename, None)
+    }
+
+    // Files referenced by another asset are looked up next to it before falling back to the roots
+    pub fn resolve_near<P: AsRef<Path>>(&self, filename: P, referrer: Option<&AssetPath>) -> Result<AssetPath> {
+        let filename = filename.as_ref();
+        let not_found = || Error::AssetNotFound {
+            path: filename.to_path_buf(),
+            roots: self.roots.clone()
+        };
+        if filename.is_absolute() {
+            return find_file(filename).ok_or_else(not_found);
+        }
+
+        referrer
+            .and_then(|referrer| referrer.sibling(filename))
+            .or_else(|| self.roots.iter().find_map(|root| match root {
+                AssetRoot::Dir(dir) => find_file(&dir.join(filename)),
+                AssetRoot::Embedded => find_embedded(filename)
+            }))
+            .ok_or_else(not_found)
+    }
+}
+
+impl Default for AssetRoots {
+    fn default() -> Self {
+        Self::with_defaults(Vec::new())
+    }
+}